@@ -0,0 +1,16 @@
+use exemplify_lib::layers::domain::entities::ChunkCache;
+use exemplify_lib::layers::domain::error::ExemplifyError;
+use exemplify_lib::layers::domain::parser_settings::ParserSettings;
+
+/// Load the chunk cache from disk. A missing cache file gives an empty cache
+pub fn load_cache(path: &str, parser_settings: &ParserSettings) -> Result<ChunkCache, ExemplifyError> {
+    match std::fs::read_to_string(path) {
+        Ok(json) => Ok(ChunkCache::from_json(&json, parser_settings)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(ChunkCache::new(parser_settings)),
+        Err(e) => Err(ExemplifyError::io(path, e))
+    }
+}
+
+pub fn save_cache(path: &str, cache: &ChunkCache) -> Result<(), ExemplifyError> {
+    std::fs::write(path, cache.to_json()?).map_err(|e| ExemplifyError::io(path, e))
+}
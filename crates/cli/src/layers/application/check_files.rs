@@ -0,0 +1,124 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::pin::Pin;
+
+use futures::{Stream, StreamExt};
+use similar::TextDiff;
+
+use exemplify_lib::layers::domain::entities::Printable;
+use exemplify_lib::layers::domain::error::ExemplifyError;
+
+use crate::layers::application::prune_files::read_generated_files;
+
+/// The result of comparing the generated examples with the content of the output folder
+#[derive(Default)]
+pub struct CheckReport {
+    pub outdated: Vec<String>,
+    pub missing: Vec<String>,
+    pub extra: Vec<String>
+}
+
+impl CheckReport {
+    pub fn is_up_to_date(&self) -> bool {
+        self.outdated.is_empty() && self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+/// Compare the examples with the files in the output folder without writing anything.
+/// A unified diff is printed for every file that is out of date.
+/// Files recorded as generated by an earlier run of the profile, which this run no longer generates, are reported as extra.
+/// Other files in the output folder are left alone, as they were not generated by exemplify
+pub async fn check_files<T: Printable + 'static>(input: Pin<Box<dyn Stream<Item=T>>>, out_dir: &str, profile: Option<&str>) -> Result<CheckReport, ExemplifyError> {
+    let mut report = CheckReport::default();
+    let mut expected_files = HashSet::new();
+
+    let examples: Vec<T> = input.collect().await;
+
+    for example in examples {
+        let output_path = format!("{}/{}", out_dir, example.file_name());
+        let content = example.print();
+
+        match std::fs::read_to_string(&output_path) {
+            Ok(existing) => {
+                if existing != content {
+                    let diff = TextDiff::from_lines(&existing, &content);
+
+                    print!("{}", diff.unified_diff().header(&output_path, &output_path));
+                    report.outdated.push(output_path);
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => report.missing.push(output_path),
+            Err(e) => return Err(ExemplifyError::io(&output_path, e))
+        }
+
+        expected_files.insert(example.file_name());
+    }
+
+    for file in read_generated_files(out_dir, profile)? {
+        if !expected_files.contains(&file) && Path::new(out_dir).join(&file).is_file() {
+            report.extra.push(format!("{}/{}", out_dir, file));
+        }
+    }
+
+    report.extra.sort();
+
+    for path in &report.outdated {
+        println!("Out of date: {}", path);
+    }
+
+    for path in &report.missing {
+        println!("Missing: {}", path);
+    }
+
+    for path in &report.extra {
+        println!("Extra: {}", path);
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use exemplify_lib::layers::domain::entities::Example;
+
+    use crate::layers::application::prune_files::STATE_FILE_NAME;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check_files() {
+        let out_dir = std::env::temp_dir().join(format!("exemplify-check-{}", std::process::id()));
+        let out_dir_name = out_dir.to_string_lossy().to_string();
+
+        std::fs::create_dir_all(&out_dir).unwrap();
+
+        for (file, content) in &[("current.md", "a"), ("stale.md", "old"), ("removed.md", "b"), ("manual.md", "c")] {
+            std::fs::write(out_dir.join(file), content).unwrap();
+        }
+
+        std::fs::write(out_dir.join(STATE_FILE_NAME), "current.md\nremoved.md\nstale.md").unwrap();
+
+        let check = |examples: Vec<(&str, &str)>| {
+            let examples: Vec<Example> = examples.into_iter()
+                .map(|(name, content)| Example::new(name.into(), vec![content.into()], None, None, None, vec![]))
+                .collect();
+
+            check_files(Box::pin(futures::stream::iter(examples)), &out_dir_name, None)
+        };
+
+        let path = |file: &str| vec![format!("{}/{}", out_dir_name, file)];
+
+        let report = check(vec![("current.md", "a"), ("stale.md", "new"), ("missing.md", "d")]).await.unwrap();
+
+        assert!(!report.is_up_to_date());
+        assert_eq!(report.outdated, path("stale.md"));
+        assert_eq!(report.missing, path("missing.md"));
+        assert_eq!(report.extra, path("removed.md"));
+
+        let report = check(vec![("current.md", "a"), ("stale.md", "old"), ("removed.md", "b")]).await.unwrap();
+
+        assert!(report.is_up_to_date());
+
+        std::fs::remove_dir_all(out_dir).unwrap();
+    }
+}
@@ -1,35 +1,107 @@
 use std::str::FromStr;
 
+use exemplify_lib::layers::domain::parser_settings::MarkerDialect;
+use exemplify_lib::layers::domain::transforms::latex_transform::LatexEnvironment;
+
+/// Command line options. Options left out fall back to the configuration file, and then to their defaults
 #[derive(Clap, Clone)]
 pub struct ExemplifyCliParams {
-    #[clap(short)]
-    pub source_directory: String,
+    #[clap(short,about="Folder to scan for examples. Can be repeated to merge the examples of several folders")]
+    pub source_directory: Vec<String>,
 
     #[clap(short)]
     pub extensions: Vec<String>,
 
-    #[clap(long)]
+    #[clap(long,about="Only scan files matching this glob, relative to the source directory. Can be repeated, and a glob starting with ! excludes files")]
+    pub include: Vec<String>,
+
+    #[clap(long,about="Skip files matching this glob, relative to the source directory. Can be repeated")]
+    pub exclude: Vec<String>,
+
+    #[clap(long,overrides_with="ignore",about="Also scan files ignored by .gitignore and .ignore files")]
+    pub no_ignore: bool,
+
+    #[clap(long,overrides_with="no-ignore",about="Skip files ignored by .gitignore and .ignore files, overriding no-ignore of the configuration file")]
+    pub ignore: bool,
+
+    #[clap(long,about="Also print the examples to stdout when writing them to an output folder (-o)")]
     pub print: bool,
 
-    #[clap(long, default_value="##exemplify-start##")]
-    pub start_token: String,
+    #[clap(long,about="Defaults to ##exemplify-start##")]
+    pub start_token: Option<String>,
+
+    #[clap(long,about="Defaults to ##exemplify-end##")]
+    pub end_token: Option<String>,
+
+    #[clap(long,about="Defaults to ##exemplify-callout##")]
+    pub callout_token: Option<String>,
+
+    #[clap(long,parse(try_from_str=parse_language_mapping),about="Language of files with an extension, given as EXTENSION=LANGUAGE, for examples without a language attribute. Can be repeated")]
+    pub language: Vec<(String, String)>,
+
+    #[clap(long,about="Marker syntax to recognise. Legal values: exemplify (default), mdbook-anchor, asciidoc-tag. Can be repeated to recognise several at once")]
+    pub dialect: Vec<MarkerDialect>,
+
+    #[clap(long,overrides_with="no-markers-anywhere",about="Recognise markers anywhere on a line, not only in comments of the source language")]
+    pub markers_anywhere: bool,
+
+    #[clap(long,overrides_with="markers-anywhere",about="Only recognise markers in comments, overriding markers-anywhere of the configuration file")]
+    pub no_markers_anywhere: bool,
 
-    #[clap(long, default_value="##exemplify-end##")]
-    pub end_token: String,
+    #[clap(long,overrides_with="no-all-errors",about="Keep going after the first error, and report all errors found before exiting")]
+    pub all_errors: bool,
 
-    #[clap(long, default_value="##exemplify-callout##")]
-    pub callout_token: String,
+    #[clap(long,overrides_with="all-errors",about="Stop at the first error, overriding all-errors of the configuration file")]
+    pub no_all_errors: bool,
 
-    #[clap(long,about="Legal values: asciidoc")]
+    #[clap(long,about="Legal values: asciidoc, markdown, rst, html, latex")]
     pub output_format: Option<OutputFormat>,
 
+    #[clap(long,about="LaTeX package used by the latex output format. Legal values: listings (default), minted")]
+    pub latex_environment: Option<LatexEnvironment>,
+
     #[clap(short,about="Folder to generate example files into. If this parameter is not provided, examples are printed to stdout")]
     pub output_folder: Option<String>,
+
+    #[clap(long,about="Verify that the files in the output folder are up to date, without writing anything")]
+    pub check: bool,
+
+    #[clap(long,overrides_with="no-prune",about="Delete files generated by earlier runs which are no longer produced. Files not created by exemplify are never touched")]
+    pub prune: bool,
+
+    #[clap(long,overrides_with="prune",about="Keep files generated by earlier runs, overriding prune of the configuration file")]
+    pub no_prune: bool,
+
+    #[clap(long,conflicts_with="check",about="Keep running, and regenerate the examples whenever a source file changes")]
+    pub watch: bool,
+
+    #[clap(long,about="Cache the chunks extracted from each source file in this file, so unchanged files are not parsed again")]
+    pub cache: Option<String>,
+
+    #[clap(long,about="Write a JSON manifest describing every generated example to this path")]
+    pub manifest: Option<String>,
+
+    #[clap(long,about="Configuration file to use, instead of the exemplify.toml found in the working directory or its ancestors")]
+    pub config: Option<String>,
+
+    #[clap(long,about="Only run this profile of the configuration file. Can be repeated. By default all profiles are run")]
+    pub profile: Vec<String>,
+}
+
+fn parse_language_mapping(value: &str) -> Result<(String, String), String> {
+    match value.find('=') {
+        Some(split) if split > 0 => Ok((value[..split].to_string(), value[split + 1..].to_string())),
+        _ => Err(format!("expected EXTENSION=LANGUAGE, got {}", value))
+    }
 }
 
 #[derive(Clone)]
 pub enum OutputFormat {
-    Asciidoctor
+    Asciidoctor,
+    Markdown,
+    Rst,
+    Html,
+    Latex
 }
 
 impl FromStr for OutputFormat {
@@ -38,6 +110,10 @@ impl FromStr for OutputFormat {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "asciidoc" => Ok(OutputFormat::Asciidoctor),
+            "markdown" => Ok(OutputFormat::Markdown),
+            "rst" => Ok(OutputFormat::Rst),
+            "html" => Ok(OutputFormat::Html),
+            "latex" => Ok(OutputFormat::Latex),
             _ => Err("invalid output format".into())
         }
     }
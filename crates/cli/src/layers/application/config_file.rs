@@ -0,0 +1,257 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer};
+
+use exemplify_lib::layers::domain::error::ExemplifyError;
+use exemplify_lib::layers::domain::parser_settings::MarkerDialect;
+use exemplify_lib::layers::domain::transforms::latex_transform::LatexEnvironment;
+
+use crate::layers::application::cli_params::{ExemplifyCliParams, OutputFormat};
+
+pub const CONFIG_FILE_NAME: &str = "exemplify.toml";
+
+/// The settings of an `exemplify.toml` file.
+/// The top level values apply to every profile, and are overridden by the values of the profile
+pub struct ConfigFile {
+    pub path: String,
+    pub values: ConfigValues,
+    pub profiles: BTreeMap<String, ConfigValues>
+}
+
+/// Settings which can be given both in the configuration file and on the command line
+#[derive(Deserialize, Default, Clone)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ConfigValues {
+    pub sources: Option<Vec<String>>,
+    pub extensions: Option<Vec<String>>,
+    pub include: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+    pub no_ignore: Option<bool>,
+    pub start_token: Option<String>,
+    pub end_token: Option<String>,
+    pub callout_token: Option<String>,
+    /// Languages of file extensions, merged key by key rather than replaced
+    pub languages: Option<BTreeMap<String, String>>,
+    #[serde(default, deserialize_with = "parse_list")]
+    pub dialects: Option<Vec<MarkerDialect>>,
+    pub markers_anywhere: Option<bool>,
+    pub all_errors: Option<bool>,
+    #[serde(default, deserialize_with = "parse_option")]
+    pub output_format: Option<OutputFormat>,
+    #[serde(default, deserialize_with = "parse_option")]
+    pub latex_environment: Option<LatexEnvironment>,
+    pub output_folder: Option<String>,
+    pub prune: Option<bool>,
+    pub cache: Option<String>,
+    pub manifest: Option<String>
+}
+
+impl ConfigFile {
+    /// Find the configuration file in the working directory or the closest of its ancestors
+    pub fn discover() -> Result<Option<ConfigFile>, ExemplifyError> {
+        let working_directory = std::env::current_dir().map_err(|e| ExemplifyError::io(".", e))?;
+
+        for folder in working_directory.ancestors() {
+            let path = folder.join(CONFIG_FILE_NAME);
+
+            if path.is_file() {
+                return ConfigFile::load(&path.to_string_lossy()).map(Some);
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Load a configuration file. Relative paths in it are taken as relative to the folder of the file
+    pub fn load(path: &str) -> Result<ConfigFile, ExemplifyError> {
+        let content = std::fs::read_to_string(path).map_err(|e| ExemplifyError::io(path, e))?;
+        let invalid_config = |message: String| ExemplifyError::InvalidConfig { path: path.to_string(), message };
+
+        let mut table: toml::value::Table = toml::from_str(&content).map_err(|e| invalid_config(e.to_string()))?;
+
+        let profiles: BTreeMap<String, ConfigValues> = match table.remove("profiles") {
+            Some(profiles) => profiles.try_into().map_err(|e| invalid_config(format!("in profiles: {}", e)))?,
+            _ => BTreeMap::new()
+        };
+
+        let values: ConfigValues = toml::Value::Table(table).try_into().map_err(|e| invalid_config(e.to_string()))?;
+        let folder = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+
+        Ok(ConfigFile {
+            path: path.to_string(),
+            values: values.relative_to(folder),
+            profiles: profiles.into_iter()
+                .map(|(name, profile)| (name, profile.relative_to(folder)))
+                .collect()
+        })
+    }
+}
+
+impl ConfigValues {
+    /// Take the values given on the command line
+    pub fn from_cli(params: &ExemplifyCliParams) -> ConfigValues {
+        let list = |values: &Vec<String>| if values.is_empty() { None } else { Some(values.clone()) };
+        // A flag and its negation override each other, so at most one of them is set
+        let flag = |value: bool, negation: bool| match (value, negation) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None
+        };
+
+        ConfigValues {
+            sources: list(&params.source_directory),
+            extensions: list(&params.extensions),
+            include: list(&params.include),
+            exclude: list(&params.exclude),
+            no_ignore: flag(params.no_ignore, params.ignore),
+            start_token: params.start_token.clone(),
+            end_token: params.end_token.clone(),
+            callout_token: params.callout_token.clone(),
+            languages: if params.language.is_empty() { None } else { Some(params.language.iter().cloned().collect()) },
+            dialects: if params.dialect.is_empty() { None } else { Some(params.dialect.clone()) },
+            markers_anywhere: flag(params.markers_anywhere, params.no_markers_anywhere),
+            all_errors: flag(params.all_errors, params.no_all_errors),
+            output_format: params.output_format.clone(),
+            latex_environment: params.latex_environment,
+            output_folder: params.output_folder.clone(),
+            prune: flag(params.prune, params.no_prune),
+            cache: params.cache.clone(),
+            manifest: params.manifest.clone()
+        }
+    }
+
+    /// Values set in `other` replace the values of `self`
+    pub fn overridden_by(self, other: ConfigValues) -> ConfigValues {
+        ConfigValues {
+            sources: other.sources.or(self.sources),
+            extensions: other.extensions.or(self.extensions),
+            include: other.include.or(self.include),
+            exclude: other.exclude.or(self.exclude),
+            no_ignore: other.no_ignore.or(self.no_ignore),
+            start_token: other.start_token.or(self.start_token),
+            end_token: other.end_token.or(self.end_token),
+            callout_token: other.callout_token.or(self.callout_token),
+            languages: match (self.languages, other.languages) {
+                (Some(mut languages), Some(overrides)) => {
+                    languages.extend(overrides);
+                    Some(languages)
+                }
+                (languages, overrides) => overrides.or(languages)
+            },
+            dialects: other.dialects.or(self.dialects),
+            markers_anywhere: other.markers_anywhere.or(self.markers_anywhere),
+            all_errors: other.all_errors.or(self.all_errors),
+            output_format: other.output_format.or(self.output_format),
+            latex_environment: other.latex_environment.or(self.latex_environment),
+            output_folder: other.output_folder.or(self.output_folder),
+            prune: other.prune.or(self.prune),
+            cache: other.cache.or(self.cache),
+            manifest: other.manifest.or(self.manifest)
+        }
+    }
+
+    fn relative_to(self, folder: &Path) -> ConfigValues {
+        let resolve = |path: String| -> String {
+            match Path::new(&path).is_absolute() {
+                true => path,
+                false => folder.join(&path).to_string_lossy().to_string()
+            }
+        };
+
+        ConfigValues {
+            sources: self.sources.map(|sources| sources.into_iter().map(resolve).collect()),
+            output_folder: self.output_folder.map(resolve),
+            cache: self.cache.map(resolve),
+            manifest: self.manifest.map(resolve),
+            ..self
+        }
+    }
+}
+
+fn parse_option<'de, D: Deserializer<'de>, T: FromStr<Err=String>>(deserializer: D) -> Result<Option<T>, D::Error> {
+    let value: Option<String> = Option::deserialize(deserializer)?;
+
+    value.map(|value| T::from_str(&value).map_err(serde::de::Error::custom)).transpose()
+}
+
+fn parse_list<'de, D: Deserializer<'de>, T: FromStr<Err=String>>(deserializer: D) -> Result<Option<Vec<T>>, D::Error> {
+    let values: Option<Vec<String>> = Option::deserialize(deserializer)?;
+
+    values.map(|values| values.iter().map(|value| T::from_str(value).map_err(serde::de::Error::custom)).collect()).transpose()
+}
+
+#[cfg(test)]
+mod test {
+    use clap::Clap;
+
+    use super::*;
+
+    #[test]
+    fn test_load_profiles() {
+        let folder = std::env::temp_dir().join(format!("exemplify-config-{}", std::process::id()));
+        let path = folder.join(CONFIG_FILE_NAME);
+
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(&path, "\
+sources = [\"src\", \"/abs/samples\"]
+extensions = [\"rs\"]
+start-token = \"##start##\"
+
+[languages]
+h = \"cpp\"
+
+[profiles.docs-adoc]
+output-format = \"asciidoc\"
+dialects = [\"exemplify\", \"asciidoc-tag\"]
+output-folder = \"docs/examples\"
+
+[profiles.site-md]
+output-format = \"markdown\"
+languages = { tpl = \"handlebars\" }
+start-token = \"##site-start##\"
+").unwrap();
+
+        let config = ConfigFile::load(&path.to_string_lossy()).unwrap();
+        let site = config.values.clone().overridden_by(config.profiles["site-md"].clone());
+        let docs = config.values.clone().overridden_by(config.profiles["docs-adoc"].clone());
+
+        assert_eq!(config.profiles.keys().collect::<Vec<_>>(), vec!["docs-adoc", "site-md"]);
+        assert_eq!(config.values.sources.unwrap(), vec![folder.join("src").to_string_lossy().to_string(), "/abs/samples".to_string()]);
+        assert_eq!(site.start_token.unwrap(), "##site-start##");
+        assert_eq!(docs.start_token.unwrap(), "##start##");
+        assert_eq!(docs.dialects.unwrap(), vec![MarkerDialect::Exemplify, MarkerDialect::AsciidocTag]);
+        assert_eq!(docs.output_folder.unwrap(), folder.join("docs/examples").to_string_lossy());
+        assert!(matches!(site.output_format, Some(OutputFormat::Markdown)));
+        assert_eq!(site.languages.unwrap().into_iter().collect::<Vec<_>>(), vec![("h".to_string(), "cpp".to_string()), ("tpl".to_string(), "handlebars".to_string())]);
+
+        std::fs::write(&path, "output-format = \"pdf\"").unwrap();
+        assert!(matches!(ConfigFile::load(&path.to_string_lossy()), Err(ExemplifyError::InvalidConfig { .. })));
+
+        std::fs::write(&path, "[profiles.x]\nunknown = 1").unwrap();
+        assert!(matches!(ConfigFile::load(&path.to_string_lossy()), Err(ExemplifyError::InvalidConfig { .. })));
+
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn test_cli_flag_negations() {
+        let values = |args: &[&str]| {
+            let params = ExemplifyCliParams::parse_from([&["exemplify"], args].concat());
+            let cli_values = ConfigValues::from_cli(&params);
+            let config_values = ConfigValues { prune: Some(true), all_errors: Some(true), ..Default::default() };
+
+            config_values.overridden_by(cli_values)
+        };
+
+        assert_eq!(values(&[]).prune, Some(true));
+        assert_eq!(values(&["--no-prune"]).prune, Some(false));
+        assert_eq!(values(&["--no-prune", "--prune"]).prune, Some(true));
+        assert_eq!(values(&["--prune", "--no-prune"]).prune, Some(false));
+        assert_eq!(values(&["--no-all-errors"]).all_errors, Some(false));
+        assert_eq!(values(&["--no-markers-anywhere"]).markers_anywhere, Some(false));
+        assert_eq!(values(&["--ignore"]).no_ignore, Some(false));
+        assert_eq!(values(&["--ignore", "--no-ignore"]).no_ignore, Some(true));
+    }
+}
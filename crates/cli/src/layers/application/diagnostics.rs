@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::ops::Range;
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use codespan_reporting::files::{Files, SimpleFiles};
+use codespan_reporting::term;
+use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
+
+use exemplify_lib::layers::domain::error::{ExemplifyError, SourceLocation};
+use exemplify_lib::layers::implementations::file_reader_factory::{SourceRoot, resolve_source_name};
+
+/// Print compiler style diagnostics for the error to stderr, showing the offending source lines.
+/// Colors are used when stderr is a terminal
+pub fn print_diagnostics(error: &ExemplifyError, roots: &[SourceRoot]) {
+    let color_choice = match std::io::stderr().is_terminal() {
+        true => ColorChoice::Auto,
+        false => ColorChoice::Never
+    };
+
+    let writer = StandardStream::stderr(color_choice);
+    let config = term::Config::default();
+    let mut sources = DiagnosticSources::new(roots);
+
+    let errors = match error {
+        ExemplifyError::Multiple { errors } => errors.iter().collect(),
+        error => vec![error]
+    };
+
+    for error in &errors {
+        let diagnostic = create_diagnostic(error, &mut sources);
+
+        if term::emit(&mut writer.lock(), &config, &sources.files, &diagnostic).is_err() {
+            eprintln!("{}", error);
+        }
+    }
+
+    if errors.len() > 1 {
+        eprintln!("Found {} errors", errors.len());
+    }
+}
+
+fn create_diagnostic(error: &ExemplifyError, sources: &mut DiagnosticSources) -> Diagnostic<usize> {
+    let (message, primary, secondary) = match error {
+        ExemplifyError::NestedChunk { location, open_chunk } => (
+            "attempting to start chunk-in-chunk".to_string(),
+            Some((location, "a new chunk is started here".to_string())),
+            Some((open_chunk, "while this chunk is still open".to_string()))
+        ),
+        ExemplifyError::EndOutsideChunk { location } => (
+            "attempting to end chunk outside of chunk".to_string(),
+            Some((location, "there is no open chunk to end".to_string())),
+            None
+        ),
+        ExemplifyError::DuplicatePart { location, part, previous } => (
+            format!("duplicate part {}", part),
+            Some((location, format!("part {} is used again here", part))),
+            Some((previous, format!("part {} is first used here", part)))
+        ),
+        ExemplifyError::MissingPartNumber { location } => (
+            "missing part number".to_string(),
+            Some((location, "chunks in examples with more than one chunk must have a part number".to_string())),
+            None
+        ),
+        ExemplifyError::MissingName { location } => (
+            "missing name".to_string(),
+            Some((location, "the chunk has no name attribute".to_string())),
+            None
+        ),
+        ExemplifyError::InvalidIntegerAttribute { location, attribute, value } => (
+            format!("invalid {} number", attribute),
+            Some((location, format!("{} is not a valid {} number", value, attribute))),
+            None
+        ),
+        ExemplifyError::InvalidAttributes { location, message } => (
+            "invalid marker attributes".to_string(),
+            Some((location, message.clone())),
+            None
+        ),
+        ExemplifyError::InvalidCallout { location, message } => (
+            "invalid callout".to_string(),
+            Some((location, message.clone())),
+            None
+        ),
+        error => (error.to_string(), None, None)
+    };
+
+    let labels = vec![
+        primary.and_then(|(location, text)| sources.label(location, true).map(|label| label.with_message(text))),
+        secondary.and_then(|(location, text)| sources.label(location, false).map(|label| label.with_message(text)))
+    ].into_iter().flatten().collect();
+
+    Diagnostic::error()
+        .with_message(message)
+        .with_labels(labels)
+}
+
+/// Source files referenced by diagnostics, read from disk on demand
+struct DiagnosticSources<'a> {
+    roots: &'a [SourceRoot],
+    files: SimpleFiles<String, String>,
+    file_ids: HashMap<String, Option<usize>>
+}
+
+impl<'a> DiagnosticSources<'a> {
+    fn new(roots: &'a [SourceRoot]) -> Self {
+        DiagnosticSources {
+            roots,
+            files: SimpleFiles::new(),
+            file_ids: HashMap::new()
+        }
+    }
+
+    fn label(&mut self, location: &SourceLocation, primary: bool) -> Option<Label<usize>> {
+        let file_id = self.file_id(&location.source_name)?;
+        let range = self.byte_range(file_id, location)?;
+
+        Some(match primary {
+            true => Label::primary(file_id, range),
+            false => Label::secondary(file_id, range)
+        })
+    }
+
+    fn file_id(&mut self, source_name: &str) -> Option<usize> {
+        if let Some(file_id) = self.file_ids.get(source_name) {
+            return *file_id;
+        }
+
+        let file_id = std::fs::read_to_string(resolve_source_name(self.roots, source_name))
+            .ok()
+            .map(|content| self.files.add(source_name.to_string(), content));
+
+        self.file_ids.insert(source_name.to_string(), file_id);
+
+        file_id
+    }
+
+    /// The byte range in the file pointed at by the location.
+    /// Falls back to the trimmed line if the location has no column information
+    fn byte_range(&self, file_id: usize, location: &SourceLocation) -> Option<Range<usize>> {
+        let line_range = self.files.line_range(file_id, location.line.checked_sub(1)?).ok()?;
+        let line = &self.files.source(file_id).ok()?[line_range.clone()];
+
+        let columns = match &location.columns {
+            Some(columns) => columns.clone(),
+            None => {
+                let trimmed = line.trim_start();
+                let start = line.len() - trimmed.len();
+
+                start..start + trimmed.trim_end().len()
+            }
+        };
+
+        Some(line_range.start + columns.start..line_range.start + columns.end)
+    }
+}
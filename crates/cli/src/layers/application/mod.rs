@@ -1,2 +1,10 @@
+pub mod cache_file;
+pub mod check_files;
 pub mod cli_params;
+pub mod config_file;
+pub mod diagnostics;
 pub mod print_files;
+pub mod prune_files;
+pub mod run_settings;
+pub mod watch;
+pub mod write_manifest;
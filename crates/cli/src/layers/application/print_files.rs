@@ -4,23 +4,51 @@ use std::pin::Pin;
 use futures::{Stream, StreamExt};
 
 use exemplify_lib::layers::domain::entities::Printable;
+use exemplify_lib::layers::domain::error::ExemplifyError;
 
-use crate::layers::application::cli_params::ExemplifyCliParams;
+use crate::layers::application::run_settings::RunSettings;
 
 
-pub async fn print_files<T: Printable + 'static>(input: Pin<Box<dyn Stream<Item=T>>>, params: ExemplifyCliParams) {
-    let _result = input.map(move |example| -> Result<(), String> {
-        let content = example.print();
+/// Where a printable was written
+pub struct OutputFile {
+    /// Name of the example, unless the printable is a supporting file
+    pub example_name: Option<String>,
+    pub path: Result<String, ExemplifyError>
+}
+
+/// Print the examples to their files, or stdout if no output folder is given or `print` is set.
+/// Files whose content is unchanged are not rewritten.
+/// Returns the output file of each printable, in stream order
+pub async fn print_files<T: Printable + 'static>(input: Pin<Box<dyn Stream<Item=T>>>, settings: &RunSettings) -> Vec<OutputFile> {
+    input.map(move |example| OutputFile {
+        example_name: example.example_name(),
+        path: print_file(&example, settings)
+    }).collect::<Vec<OutputFile>>().await
+}
+
+fn print_file<T: Printable>(example: &T, settings: &RunSettings) -> Result<String, ExemplifyError> {
+    let content = example.print();
 
-        if let Some(out_dir) = &params.output_folder {
-            let output_path = format!("{}/{}", out_dir, example.file_name());
-            let output_path = Path::new(&output_path);
-            std::fs::create_dir_all(output_path.parent().ok_or("".to_string())?).map_err(|e| e.to_string())?;
-            std::fs::write(output_path, content).map_err(|e| e.to_string())?;
-        } else {
-            println!("Example {}:\n{}\n", example.file_name(), content);
+    if settings.print && settings.output_folder.is_some() {
+        println!("Example {}:\n{}\n", example.file_name(), content);
+    }
+
+    if let Some(out_dir) = &settings.output_folder {
+        let output_path = format!("{}/{}", out_dir, example.file_name());
+        let path = Path::new(&output_path);
+
+        if std::fs::read(path).map(|existing| existing == content.as_bytes()).unwrap_or(false) {
+            return Ok(output_path);
         }
 
-        Ok(())
-    }).collect::<Vec<Result<(), String>>>().await;
+        let parent = path.parent().ok_or_else(|| ExemplifyError::InvalidFileName { path: output_path.clone() })?;
+        std::fs::create_dir_all(parent).map_err(|e| ExemplifyError::io(&output_path, e))?;
+        std::fs::write(path, content).map_err(|e| ExemplifyError::io(&output_path, e))?;
+
+        Ok(output_path)
+    } else {
+        println!("Example {}:\n{}\n", example.file_name(), content);
+
+        Ok(example.file_name())
+    }
 }
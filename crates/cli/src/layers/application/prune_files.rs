@@ -0,0 +1,198 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Component, Path};
+
+use exemplify_lib::layers::domain::error::ExemplifyError;
+
+/// Name of the file listing every file generated into an output folder, relative to the folder
+pub const STATE_FILE_NAME: &str = ".exemplify-generated";
+
+/// The generated files of an output folder, by the profile generating them. Runs without a profile use the empty name
+type GeneratedFiles = BTreeMap<String, BTreeSet<String>>;
+
+/// Record the files generated by this run in the state file of the output folder, under the profile of the run.
+/// Files generated by a previous run of the profile but not by this one are deleted if `prune` is set, otherwise they are kept
+/// in the state file so a later pruning run can remove them. Files not listed for the profile, including those of
+/// other profiles sharing the output folder, are never touched. The state file is only written if its content changes.
+pub fn update_generated_files(out_dir: &str, profile: Option<&str>, output_files: &[Result<String, ExemplifyError>], prune: bool) -> Result<(), ExemplifyError> {
+    let state_path = format!("{}/{}", out_dir, STATE_FILE_NAME);
+    let previous_content = read_state_content(&state_path)?;
+    let mut state = parse_state(&previous_content);
+    let profile = profile.unwrap_or_default().to_string();
+
+    let previous = state.remove(&profile).unwrap_or_default();
+    let owned_by_others: BTreeSet<&String> = state.values().flatten().collect();
+
+    let generated: BTreeSet<String> = output_files.iter()
+        .filter_map(|output_file| output_file.as_ref().ok())
+        .filter_map(|output_file| Path::new(output_file).strip_prefix(out_dir).ok())
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+
+    // If some file could not be written we cannot tell whether it is stale, so nothing is pruned
+    let prune = prune && output_files.iter().all(|output_file| output_file.is_ok());
+
+    let mut files = generated.clone();
+
+    for stale in previous.difference(&generated) {
+        if owned_by_others.contains(stale) {
+            continue;
+        }
+
+        if prune {
+            remove_stale_file(out_dir, stale)?;
+        } else {
+            files.insert(stale.clone());
+        }
+    }
+
+    if !files.is_empty() {
+        state.insert(profile, files);
+    }
+
+    let content = format_state(&state);
+
+    if content == previous_content {
+        return Ok(());
+    }
+
+    std::fs::write(&state_path, content).map_err(|e| ExemplifyError::io(&state_path, e))
+}
+
+/// The files recorded in the state file of the output folder as generated by the profile, relative to the folder
+pub fn read_generated_files(out_dir: &str, profile: Option<&str>) -> Result<BTreeSet<String>, ExemplifyError> {
+    let state_path = format!("{}/{}", out_dir, STATE_FILE_NAME);
+    let mut state = parse_state(&read_state_content(&state_path)?);
+
+    Ok(state.remove(profile.unwrap_or_default()).unwrap_or_default())
+}
+
+fn read_state_content(state_path: &str) -> Result<String, ExemplifyError> {
+    match std::fs::read_to_string(state_path) {
+        Ok(content) => Ok(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(ExemplifyError::io(state_path, e))
+    }
+}
+
+/// Each line holds a file, preceded by the profile generating it and a tab if the run had a profile
+fn parse_state(content: &str) -> GeneratedFiles {
+    let mut state = GeneratedFiles::new();
+
+    for line in content.lines().filter(|line| !line.is_empty()) {
+        let (profile, file) = match line.find('\t') {
+            Some(tab) => (&line[..tab], &line[tab + 1..]),
+            _ => ("", line)
+        };
+
+        state.entry(profile.to_string()).or_default().insert(file.to_string());
+    }
+
+    state
+}
+
+fn format_state(state: &GeneratedFiles) -> String {
+    let lines: Vec<String> = state.iter()
+        .flat_map(|(profile, files)| files.iter().map(move |file| match profile.as_str() {
+            "" => file.clone(),
+            _ => format!("{}\t{}", profile, file)
+        }))
+        .collect();
+
+    lines.join("\n")
+}
+
+fn remove_stale_file(out_dir: &str, relative_path: &str) -> Result<(), ExemplifyError> {
+    // Refuse entries pointing outside of the output folder, in case the state file was edited by hand
+    if Path::new(relative_path).components().any(|component| !matches!(component, Component::Normal(_))) {
+        return Ok(());
+    }
+
+    let out_dir = Path::new(out_dir);
+    let path = out_dir.join(relative_path);
+
+    if !path.is_file() {
+        return Ok(());
+    }
+
+    std::fs::remove_file(&path).map_err(|e| ExemplifyError::io(&path.to_string_lossy(), e))?;
+    println!("Removed stale file {}", path.to_string_lossy());
+
+    // Clean up the folders that were only there for the removed file
+    let mut folder = path.parent();
+
+    while let Some(current) = folder {
+        if current == out_dir || std::fs::remove_dir(current).is_err() {
+            break;
+        }
+
+        folder = current.parent();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_prune_only_removes_generated_files() {
+        let out_dir = std::env::temp_dir().join(format!("exemplify-prune-{}", std::process::id()));
+        let out_dir_name = out_dir.to_string_lossy().to_string();
+
+        std::fs::create_dir_all(out_dir.join("nested")).unwrap();
+
+        for file in &["kept.adoc", "nested/renamed.adoc", "manual.adoc"] {
+            std::fs::write(out_dir.join(file), "").unwrap();
+        }
+
+        let first_run = vec![Ok(format!("{}/kept.adoc", out_dir_name)), Ok(format!("{}/nested/renamed.adoc", out_dir_name))];
+        update_generated_files(&out_dir_name, None, &first_run, true).unwrap();
+
+        let second_run = vec![Ok(format!("{}/kept.adoc", out_dir_name))];
+        update_generated_files(&out_dir_name, None, &second_run, true).unwrap();
+
+        assert!(out_dir.join("kept.adoc").exists());
+        assert!(out_dir.join("manual.adoc").exists());
+        assert!(!out_dir.join("nested").exists());
+        assert_eq!(std::fs::read_to_string(out_dir.join(STATE_FILE_NAME)).unwrap(), "kept.adoc");
+
+        std::fs::remove_dir_all(out_dir).unwrap();
+    }
+
+    #[test]
+    fn test_profiles_sharing_an_output_folder() {
+        let out_dir = std::env::temp_dir().join(format!("exemplify-prune-profiles-{}", std::process::id()));
+        let out_dir_name = out_dir.to_string_lossy().to_string();
+        let state_path = out_dir.join(STATE_FILE_NAME);
+        let file = |name: &str| Ok(format!("{}/{}", out_dir_name, name));
+
+        std::fs::create_dir_all(&out_dir).unwrap();
+
+        for name in &["api.md", "guide.md", "shared.md"] {
+            std::fs::write(out_dir.join(name), "").unwrap();
+        }
+
+        update_generated_files(&out_dir_name, Some("api"), &[file("api.md"), file("shared.md")], true).unwrap();
+        update_generated_files(&out_dir_name, Some("guide"), &[file("guide.md"), file("shared.md")], true).unwrap();
+
+        // Neither profile removes the files of the other
+        assert!(out_dir.join("api.md").exists());
+        assert!(out_dir.join("guide.md").exists());
+
+        // A file stopping to be generated by one profile is kept while another still generates it
+        update_generated_files(&out_dir_name, Some("api"), &[file("api.md")], true).unwrap();
+
+        assert!(out_dir.join("shared.md").exists());
+        assert_eq!(std::fs::read_to_string(&state_path).unwrap(), "api\tapi.md\nguide\tguide.md\nguide\tshared.md");
+
+        // An unchanged state is not written again
+        let modified = std::fs::metadata(&state_path).unwrap().modified().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        update_generated_files(&out_dir_name, Some("guide"), &[file("guide.md"), file("shared.md")], true).unwrap();
+
+        assert_eq!(std::fs::metadata(&state_path).unwrap().modified().unwrap(), modified);
+
+        std::fs::remove_dir_all(out_dir).unwrap();
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+
+use exemplify_lib::layers::domain::error::ExemplifyError;
+use exemplify_lib::layers::domain::parser_settings::MarkerDialect;
+use exemplify_lib::layers::domain::transforms::latex_transform::LatexEnvironment;
+use exemplify_lib::layers::implementations::fs_discovery::DiscoverySettings;
+
+use crate::layers::application::cli_params::{ExemplifyCliParams, OutputFormat};
+use crate::layers::application::config_file::{ConfigFile, ConfigValues};
+
+/// The settings of a single run, after merging the command line with the configuration file
+#[derive(Clone)]
+pub struct RunSettings {
+    /// Name of the configuration profile, if any
+    pub profile: Option<String>,
+    pub source_directories: Vec<String>,
+    pub extensions: Vec<String>,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub use_ignore_files: bool,
+    pub start_token: String,
+    pub end_token: String,
+    pub callout_token: String,
+    pub languages: BTreeMap<String, String>,
+    pub dialects: Vec<MarkerDialect>,
+    pub markers_anywhere: bool,
+    pub all_errors: bool,
+    pub output_format: Option<OutputFormat>,
+    pub latex_environment: LatexEnvironment,
+    pub output_folder: Option<String>,
+    pub check: bool,
+    /// Print the examples to stdout, also when writing them to the output folder
+    pub print: bool,
+    pub prune: bool,
+    pub cache: Option<String>,
+    pub manifest: Option<String>
+}
+
+impl RunSettings {
+    fn new(profile: Option<String>, values: ConfigValues, params: &ExemplifyCliParams) -> Result<RunSettings, ExemplifyError> {
+        let source_directories = values.sources.unwrap_or_default();
+
+        if source_directories.is_empty() {
+            return Err(ExemplifyError::MissingSetting { setting: "source directory (-s)".into(), required_by: None });
+        }
+
+        if params.check && values.output_folder.is_none() {
+            return Err(ExemplifyError::MissingSetting { setting: "output folder (-o)".into(), required_by: Some("--check".into()) });
+        }
+
+        Ok(RunSettings {
+            profile,
+            source_directories,
+            extensions: values.extensions.unwrap_or_default(),
+            include: values.include.unwrap_or_default(),
+            exclude: values.exclude.unwrap_or_default(),
+            use_ignore_files: !values.no_ignore.unwrap_or(false),
+            start_token: values.start_token.unwrap_or_else(|| "##exemplify-start##".into()),
+            end_token: values.end_token.unwrap_or_else(|| "##exemplify-end##".into()),
+            callout_token: values.callout_token.unwrap_or_else(|| "##exemplify-callout##".into()),
+            languages: values.languages.unwrap_or_default(),
+            dialects: values.dialects.unwrap_or_else(|| vec![MarkerDialect::Exemplify]),
+            markers_anywhere: values.markers_anywhere.unwrap_or(false),
+            all_errors: values.all_errors.unwrap_or(false),
+            output_format: values.output_format,
+            latex_environment: values.latex_environment.unwrap_or(LatexEnvironment::Listings),
+            output_folder: values.output_folder,
+            check: params.check,
+            print: params.print,
+            prune: values.prune.unwrap_or(false),
+            cache: values.cache,
+            manifest: values.manifest
+        })
+    }
+
+    /// Which files of the source directories the run scans
+    pub fn discovery_settings(&self) -> DiscoverySettings {
+        DiscoverySettings {
+            extensions: self.extensions.clone(),
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+            use_ignore_files: self.use_ignore_files
+        }
+    }
+
+    /// The files and folders the run writes to
+    pub fn written_paths(&self) -> Vec<String> {
+        self.output_folder.iter()
+            .chain(self.cache.iter())
+            .chain(self.manifest.iter())
+            .cloned()
+            .collect()
+    }
+}
+
+/// Work out the runs to make: one for each selected profile of the configuration file,
+/// or a single one if there is no configuration file or it has no profiles.
+/// Command line options override the values of the configuration file
+pub fn resolve_run_settings(params: &ExemplifyCliParams) -> Result<Vec<RunSettings>, ExemplifyError> {
+    let config = match &params.config {
+        Some(path) => Some(ConfigFile::load(path)?),
+        _ => ConfigFile::discover()?
+    };
+
+    let cli_values = ConfigValues::from_cli(params);
+
+    let config = match config {
+        Some(config) => config,
+        _ => {
+            if let Some(profile) = params.profile.first() {
+                return Err(ExemplifyError::MissingSetting { setting: format!("configuration file defining the profile {}", profile), required_by: None });
+            }
+
+            return Ok(vec![RunSettings::new(None, cli_values, params)?]);
+        }
+    };
+
+    let profiles: Vec<(Option<String>, ConfigValues)> = if !params.profile.is_empty() {
+        params.profile.iter()
+            .map(|name| match config.profiles.get(name) {
+                Some(profile) => Ok((Some(name.clone()), profile.clone())),
+                _ => Err(ExemplifyError::InvalidConfig { path: config.path.clone(), message: format!("there is no profile named {}", name) })
+            })
+            .collect::<Result<_, _>>()?
+    } else if config.profiles.is_empty() {
+        vec![(None, ConfigValues::default())]
+    } else {
+        config.profiles.iter()
+            .map(|(name, profile)| (Some(name.clone()), profile.clone()))
+            .collect()
+    };
+
+    profiles.into_iter()
+        .map(|(name, profile)| {
+            let values = config.values.clone()
+                .overridden_by(profile)
+                .overridden_by(cli_values.clone());
+
+            RunSettings::new(name, values, params)
+        })
+        .collect()
+}
@@ -0,0 +1,176 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+
+use exemplify_lib::layers::domain::error::ExemplifyError;
+use exemplify_lib::layers::implementations::fs_discovery::DiscoverySettings;
+
+use crate::layers::application::run_settings::RunSettings;
+
+/// How long to wait for more events before reporting a change, so a save touching several files causes a single update
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(100);
+
+/// Watches the source folders of the runs, recursively, for changes to the files the runs scan
+pub struct SourceWatcher {
+    // The watcher stops when dropped, so it is kept alive alongside the receiver
+    _watcher: RecommendedWatcher,
+    events: Receiver<DebouncedEvent>,
+    filter: EventFilter
+}
+
+impl SourceWatcher {
+    pub fn new(runs: &[RunSettings]) -> Result<SourceWatcher, ExemplifyError> {
+        let (sender, events) = channel();
+
+        let mut watcher = notify::watcher(sender, DEBOUNCE_DELAY).map_err(watch_error)?;
+        let filter = EventFilter::new(runs);
+
+        for (source_directory, _) in &filter.sources {
+            watcher.watch(source_directory, RecursiveMode::Recursive).map_err(watch_error)?;
+        }
+
+        Ok(SourceWatcher {
+            _watcher: watcher,
+            events,
+            filter
+        })
+    }
+
+    /// Block until a watched file changes.
+    /// Events arriving at the same time are merged, so one call corresponds to one regeneration
+    pub fn wait_for_change(&self) -> Result<(), ExemplifyError> {
+        loop {
+            let event = self.events.recv().map_err(watch_error)?;
+
+            if self.is_relevant(&event)? {
+                while self.events.try_recv().is_ok() {}
+
+                return Ok(());
+            }
+        }
+    }
+
+    fn is_relevant(&self, event: &DebouncedEvent) -> Result<bool, ExemplifyError> {
+        match event {
+            DebouncedEvent::Create(path) |
+            DebouncedEvent::Write(path) |
+            DebouncedEvent::Remove(path) => self.filter.is_watched(path),
+            DebouncedEvent::Rename(from, to) => Ok(self.filter.is_watched(from)? || self.filter.is_watched(to)?),
+            // Events were lost, so anything may have changed
+            DebouncedEvent::Rescan => Ok(true),
+            _ => Ok(false)
+        }
+    }
+}
+
+/// Decides which changed paths call for a regeneration: the files some run scans, except those any run writes.
+/// All paths are made absolute, as the watcher reports absolute paths
+struct EventFilter {
+    sources: Vec<(PathBuf, DiscoverySettings)>,
+    written: Vec<PathBuf>
+}
+
+impl EventFilter {
+    fn new(runs: &[RunSettings]) -> EventFilter {
+        let sources = runs.iter()
+            .flat_map(|settings| {
+                let discovery_settings = settings.discovery_settings();
+
+                settings.source_directories.iter()
+                    .map(move |source_directory| (absolute_path(Path::new(source_directory)), discovery_settings.clone()))
+            })
+            .collect();
+
+        let written = runs.iter()
+            .flat_map(|settings| settings.written_paths())
+            .map(|path| absolute_path(Path::new(&path)))
+            .collect();
+
+        EventFilter { sources, written }
+    }
+
+    fn is_watched(&self, path: &Path) -> Result<bool, ExemplifyError> {
+        let path = absolute_path(path);
+
+        if self.written.iter().any(|written| path.starts_with(written)) {
+            return Ok(false);
+        }
+
+        for (source_directory, discovery_settings) in &self.sources {
+            if discovery_settings.matches(source_directory, &path)? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+/// The canonical form of a path which may not exist yet, or no longer: that of its closest existing ancestor,
+/// followed by the remaining components
+fn absolute_path(path: &Path) -> PathBuf {
+    let path = match std::env::current_dir() {
+        Ok(current_dir) => current_dir.join(path),
+        _ => path.to_path_buf()
+    };
+
+    for ancestor in path.ancestors() {
+        if let Ok(canonical) = ancestor.canonicalize() {
+            return match path.strip_prefix(ancestor) {
+                Ok(rest) => canonical.join(rest),
+                _ => canonical
+            };
+        }
+    }
+
+    path
+}
+
+fn watch_error<E: ToString>(error: E) -> ExemplifyError {
+    ExemplifyError::Watch { message: error.to_string() }
+}
+
+#[cfg(test)]
+mod test {
+    use clap::Clap;
+
+    use crate::layers::application::cli_params::ExemplifyCliParams;
+    use crate::layers::application::run_settings::resolve_run_settings;
+
+    use super::*;
+
+    #[test]
+    fn test_event_filter() {
+        let root = std::env::temp_dir().join(format!("exemplify-watch-{}", std::process::id()));
+        let source = root.join("src");
+
+        std::fs::create_dir_all(source.join("target")).unwrap();
+        std::fs::write(source.join(".gitignore"), "target/\n").unwrap();
+
+        let source_arg = source.to_string_lossy().to_string();
+        let output_arg = source.join("docs").to_string_lossy().to_string();
+        let cache_arg = source.join("cache.rs").to_string_lossy().to_string();
+
+        let params = ExemplifyCliParams::parse_from([
+            "exemplify", "--config", "/dev/null", "-s", &source_arg, "-e", "rs", "-e", "md",
+            "--exclude", "vendor/**", "-o", &output_arg, "--cache", &cache_arg
+        ]);
+
+        let filter = EventFilter::new(&resolve_run_settings(&params).unwrap());
+        let is_watched = |file: &str| filter.is_watched(&source.join(file)).unwrap();
+
+        assert!(is_watched("main.rs"));
+        assert!(is_watched("lib/new.rs"));
+        assert!(!is_watched("notes.txt"));
+        assert!(!is_watched("vendor/lib.rs"));
+        assert!(!is_watched("target/build.rs"));
+        assert!(!is_watched("docs/example.md"));
+        assert!(!is_watched("docs/.exemplify-generated"));
+        assert!(!is_watched("cache.rs"));
+        assert!(!is_watched("../outside.rs"));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+
+use exemplify_lib::layers::domain::entities::{Example, Manifest, ManifestEntry};
+use exemplify_lib::layers::domain::error::ExemplifyError;
+
+use crate::layers::application::run_settings::RunSettings;
+
+/// Write the manifest of the examples to the path given by the manifest setting.
+/// `example_files` maps the name of each example written to its output file. Examples which could not be written are left out
+pub fn write_manifest(examples: &[Example], example_files: &BTreeMap<String, String>, settings: &RunSettings) -> Result<(), ExemplifyError> {
+    let manifest_path = match &settings.manifest {
+        Some(path) => path,
+        _ => return Ok(())
+    };
+
+    let mut manifest = Manifest::default();
+
+    for example in examples {
+        if let Some(file) = example_files.get(&example.name) {
+            manifest.examples.push(ManifestEntry::new(example, file.clone(), &settings.callout_token)?);
+        }
+    }
+
+    std::fs::write(manifest_path, manifest.to_json()?).map_err(|e| ExemplifyError::io(manifest_path, e))
+}
+
+#[cfg(test)]
+mod test {
+    use clap::Clap;
+
+    use crate::layers::application::cli_params::ExemplifyCliParams;
+    use crate::layers::application::run_settings::resolve_run_settings;
+
+    use super::*;
+
+    #[test]
+    fn test_write_manifest() {
+        let manifest_path = std::env::temp_dir().join(format!("exemplify-manifest-{}.json", std::process::id()));
+        let manifest_arg = manifest_path.to_string_lossy().to_string();
+
+        let params = ExemplifyCliParams::parse_from(["exemplify", "--config", "/dev/null", "-s", "src", "--manifest", &manifest_arg]);
+        let settings = resolve_run_settings(&params).unwrap().remove(0);
+
+        let examples = vec![
+            Example::new("first".into(), vec!["let a = 1; // ##exemplify-callout##{value=\"One\"}".into()], Some("First".into()), Some("rust".into()), None, vec![]),
+            Example::new("second".into(), vec!["b = 2".into()], None, None, Some("second-id".into()), vec![])
+        ];
+
+        // The stylesheet of the html format comes first, so the files do not line up with the examples
+        let example_files = vec![
+            ("second".to_string(), "out/second.html".to_string()),
+            ("first".to_string(), "out/first.html".to_string())
+        ].into_iter().collect();
+
+        write_manifest(&examples, &example_files, &settings).unwrap();
+
+        let manifest: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&manifest_path).unwrap()).unwrap();
+
+        assert_eq!(manifest, serde_json::json!({
+            "examples": [
+                {
+                    "name": "first",
+                    "title": "First",
+                    "language": "rust",
+                    "id": null,
+                    "file": "out/first.html",
+                    "content": ["let a = 1;"],
+                    "callouts": [{ "number": 1, "text": "One", "line": 0 }],
+                    "chunks": []
+                },
+                {
+                    "name": "second",
+                    "title": null,
+                    "language": null,
+                    "id": "second-id",
+                    "file": "out/second.html",
+                    "content": ["b = 2"],
+                    "callouts": [],
+                    "chunks": []
+                }
+            ]
+        }));
+
+        std::fs::remove_file(manifest_path).unwrap();
+    }
+}
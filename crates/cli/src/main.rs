@@ -3,22 +3,38 @@ extern crate clap;
 
 
 
+use std::collections::BTreeMap;
+use std::pin::Pin;
 use std::process::exit;
 
 use clap::Clap;
 
 
 
+use exemplify_lib::layers::domain::entities::{ChunkCache, Example, Printable};
+use exemplify_lib::layers::domain::error::ExemplifyError;
 use exemplify_lib::layers::domain::parser_settings::ParserSettings;
-use exemplify_lib::layers::domain::collect_examples::collect_examples;
+use exemplify_lib::layers::domain::collect_examples::collect_examples_with_cache;
 use exemplify_lib::layers::domain::reader_stream::reader_stream;
 use exemplify_lib::layers::domain::transforms::asciidoctor_transform::{AsciidoctorSettings, map_to_asciidoctor};
-use exemplify_lib::layers::implementations::file_reader_factory::FileReaderFactory;
-use exemplify_lib::layers::implementations::fs_discovery::discover_fs_files;
+use exemplify_lib::layers::domain::transforms::html_transform::{HtmlSettings, HtmlStylesheet, map_to_html};
+use exemplify_lib::layers::domain::transforms::latex_transform::{LatexSettings, map_to_latex};
+use exemplify_lib::layers::domain::transforms::markdown_transform::{MarkdownSettings, map_to_markdown};
+use exemplify_lib::layers::domain::transforms::plain_transform::{PlainSettings, map_to_plain};
+use exemplify_lib::layers::domain::transforms::rst_transform::{RstSettings, map_to_rst};
+use exemplify_lib::layers::implementations::file_reader_factory::{FileReaderFactory, SourceRoot, source_roots};
+use exemplify_lib::layers::implementations::fs_discovery::discover_fs_roots;
 
+use crate::layers::application::cache_file::{load_cache, save_cache};
+use crate::layers::application::check_files::check_files;
 use crate::layers::application::cli_params::{ExemplifyCliParams, OutputFormat};
+use crate::layers::application::diagnostics::print_diagnostics;
 use crate::layers::application::print_files::print_files;
-use futures::StreamExt;
+use crate::layers::application::prune_files::update_generated_files;
+use crate::layers::application::run_settings::{RunSettings, resolve_run_settings};
+use crate::layers::application::watch::SourceWatcher;
+use crate::layers::application::write_manifest::write_manifest;
+use futures::{Stream, StreamExt};
 
 
 mod layers;
@@ -27,40 +43,261 @@ mod layers;
 async fn main() {
     let params: ExemplifyCliParams = ExemplifyCliParams::parse();
 
-    match run(params).await {
+    let runs = match resolve_run_settings(&params) {
+        Ok(runs) => runs,
         Err(e) => {
-            println!("{}", e);
+            print_diagnostics(&e, &[]);
             exit(1);
         }
-        _ => {}
+    };
+
+    if params.watch {
+        if let Err(e) = watch(&runs).await {
+            print_diagnostics(&e, &[]);
+            exit(1);
+        }
+
+        return;
+    }
+
+    if !run_all(&runs).await {
+        exit(1);
+    }
+}
+
+async fn watch(runs: &[RunSettings]) -> Result<(), ExemplifyError> {
+    let watcher = SourceWatcher::new(runs)?;
+
+    loop {
+        // Errors are reported without stopping, as the next edit may well fix them
+        run_all(runs).await;
+
+        watcher.wait_for_change()?;
+    }
+}
+
+/// Make every run, reporting the errors of each. Returns whether all runs succeeded
+async fn run_all(runs: &[RunSettings]) -> bool {
+    let mut succeeded = true;
+
+    for settings in runs {
+        let roots = source_roots(&settings.source_directories);
+
+        if let Some(profile) = &settings.profile {
+            if runs.len() > 1 {
+                eprintln!("Profile {}", profile);
+            }
+        }
+
+        if let Err(e) = run(settings, &roots).await {
+            print_diagnostics(&e, &roots);
+            succeeded = false;
+        }
     }
+
+    succeeded
 }
 
-async fn run(params: ExemplifyCliParams) -> Result<(), String> {
-    let files = discover_fs_files(params.source_directory.clone().into(), &params.extensions).unwrap();
+async fn run(settings: &RunSettings, roots: &[SourceRoot]) -> Result<(), ExemplifyError> {
+    let files = discover_fs_roots(roots, &settings.discovery_settings())?;
 
     let reader_factory = reader_stream(
-        Box::new(FileReaderFactory {}),
+        Box::new(FileReaderFactory::new(roots.to_vec())),
         files);
 
-    let parser_settings = ParserSettings { start_token: params.start_token.clone(), end_token: params.end_token.clone() };
+    let parser_settings = ParserSettings {
+        start_token: settings.start_token.clone(),
+        end_token: settings.end_token.clone(),
+        collect_all_errors: settings.all_errors,
+        extension_languages: settings.languages.clone(),
+        markers_anywhere: settings.markers_anywhere,
+        dialects: settings.dialects.clone(),
+        ..Default::default()
+    };
 
-    let examples = collect_examples(reader_factory, parser_settings.clone()).await?;
+    let mut file_cache = match &settings.cache {
+        Some(path) => load_cache(path, &parser_settings)?,
+        _ => ChunkCache::new(&parser_settings)
+    };
 
-    match &params.output_format {
+    let examples: Vec<Example> = collect_examples_with_cache(reader_factory, parser_settings.clone(), &mut file_cache).await?.collect().await;
+
+    // Check mode writes nothing, the cache included
+    if let Some(path) = settings.cache.as_ref().filter(|_| !settings.check) {
+        save_cache(path, &file_cache)?;
+    }
+
+    let example_stream = Box::pin(futures::stream::iter(examples.clone()));
+
+    let printables: Vec<Box<dyn Printable>> = match &settings.output_format {
         Some(format) => {
             match format {
                 OutputFormat::Asciidoctor => {
-                    let asciidoc = map_to_asciidoctor(examples, AsciidoctorSettings { callout_token: params.callout_token.clone() });
+                    into_printables(map_to_asciidoctor(example_stream, AsciidoctorSettings { callout_token: settings.callout_token.clone() })).await?
+                }
+                OutputFormat::Markdown => {
+                    into_printables(map_to_markdown(example_stream, MarkdownSettings { callout_token: settings.callout_token.clone() })).await?
+                }
+                OutputFormat::Rst => {
+                    into_printables(map_to_rst(example_stream, RstSettings { callout_token: settings.callout_token.clone() })).await?
+                }
+                OutputFormat::Html => {
+                    let mut html = into_printables(map_to_html(example_stream, HtmlSettings { callout_token: settings.callout_token.clone() })).await?;
+
+                    if settings.output_folder.is_some() {
+                        html.push(Box::new(HtmlStylesheet::new()?));
+                    }
 
-                    print_files(Box::pin(asciidoc.map(|e| e.unwrap())), params.clone()).await;
+                    html
+                }
+                OutputFormat::Latex => {
+                    into_printables(map_to_latex(example_stream, LatexSettings {
+                        callout_token: settings.callout_token.clone(),
+                        environment: settings.latex_environment
+                    })).await?
                 }
             }
         }
         None => {
-            print_files(examples, params.clone()).await;
+            map_to_plain(example_stream, PlainSettings { callout_token: settings.callout_token.clone() })
+                .map(|example| Box::new(example) as Box<dyn Printable>)
+                .collect().await
+        }
+    };
+
+    let printables: Pin<Box<dyn Stream<Item=Box<dyn Printable>>>> = Box::pin(futures::stream::iter(printables));
+
+    if settings.check {
+        let out_dir = settings.output_folder.as_deref().ok_or_else(|| ExemplifyError::MissingSetting {
+            setting: "output folder (-o)".into(),
+            required_by: Some("--check".into())
+        })?;
+
+        let report = check_files(printables, out_dir, settings.profile.as_deref()).await?;
+
+        if !report.is_up_to_date() {
+            return Err(ExemplifyError::OutOfDate { files: report.outdated.len() + report.missing.len() + report.extra.len() });
+        }
+
+        return Ok(());
+    }
+
+    let mut example_files = BTreeMap::new();
+
+    let output_files: Vec<Result<String, ExemplifyError>> = print_files(printables, settings).await.into_iter()
+        .map(|output_file| {
+            if let (Some(name), Ok(path)) = (&output_file.example_name, &output_file.path) {
+                example_files.insert(name.clone(), path.clone());
+            }
+
+            output_file.path
+        })
+        .collect();
+
+    if let Some(out_dir) = &settings.output_folder {
+        update_generated_files(out_dir, settings.profile.as_deref(), &output_files, settings.prune)?;
+    }
+
+    write_manifest(&examples, &example_files, settings)?;
+
+    // The files written are recorded above, before failing on those which could not be
+    let mut errors: Vec<ExemplifyError> = output_files.into_iter().filter_map(Result::err).collect();
+
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(errors.remove(0)),
+        _ if !settings.all_errors => Err(errors.remove(0)),
+        _ => Err(ExemplifyError::Multiple { errors })
+    }
+}
+
+/// Gather the transformed examples. Every example failing to transform is reported, rather than only the first
+async fn into_printables<T: Printable + 'static>(input: Pin<Box<dyn Stream<Item=Result<T, ExemplifyError>>>>) -> Result<Vec<Box<dyn Printable>>, ExemplifyError> {
+    let results: Vec<Result<T, ExemplifyError>> = input.collect().await;
+    let mut printables: Vec<Box<dyn Printable>> = Vec::new();
+    let mut errors = Vec::new();
+
+    for result in results {
+        match result {
+            Ok(printable) => printables.push(Box::new(printable)),
+            Err(error) => errors.push(error)
         }
     }
 
-    Ok(())
+    match errors.len() {
+        0 => Ok(printables),
+        1 => Err(errors.remove(0)),
+        _ => Err(ExemplifyError::Multiple { errors })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check_writes_no_cache() {
+        let root = std::env::temp_dir().join(format!("exemplify-check-cache-{}", std::process::id()));
+        let cache = root.join("cache.json");
+
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "// ##exemplify-start##{name=\"main\"}\nfn main() {}\n// ##exemplify-end##\n").unwrap();
+
+        let arg = |path: &str| root.join(path).to_string_lossy().to_string();
+        let params = ExemplifyCliParams::parse_from([
+            "exemplify", "--config", "/dev/null", "-s", &arg("src"), "-e", "rs", "-o", &arg("out"), "--cache", &arg("cache.json"), "--check"
+        ]);
+
+        let settings = resolve_run_settings(&params).unwrap().remove(0);
+        let roots = source_roots(&settings.source_directories);
+
+        assert!(matches!(run(&settings, &roots).await, Err(ExemplifyError::OutOfDate { files: 1 })));
+        assert!(!cache.exists());
+        assert!(!root.join("out").exists());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failed_writes_fail_the_run() {
+        let root = std::env::temp_dir().join(format!("exemplify-failed-writes-{}", std::process::id()));
+
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "\
+// ##exemplify-start##{name=\"alpha\"}
+fn alpha() {}
+// ##exemplify-end##
+// ##exemplify-start##{name=\"beta\"}
+fn beta() {}
+// ##exemplify-end##
+// ##exemplify-start##{name=\"gamma\"}
+fn gamma() {}
+// ##exemplify-end##
+").unwrap();
+
+        // A folder in place of the output file makes writing it fail
+        for name in &["alpha", "gamma"] {
+            std::fs::create_dir_all(root.join("out").join(name)).unwrap();
+        }
+
+        let arg = |path: &str| root.join(path).to_string_lossy().to_string();
+        let run_with = |extra: &'static [&'static str]| {
+            let mut args = vec!["exemplify".to_string(), "--config".into(), "/dev/null".into(), "-s".into(), arg("src"), "-e".into(), "rs".into(),
+                                "-o".into(), arg("out"), "--manifest".into(), arg("manifest.json")];
+            args.extend(extra.iter().map(|extra| extra.to_string()));
+
+            let settings = resolve_run_settings(&ExemplifyCliParams::parse_from(args)).unwrap().remove(0);
+
+            async move { run(&settings, &source_roots(&settings.source_directories)).await }
+        };
+
+        assert!(matches!(run_with(&[]).await, Err(ExemplifyError::Io { path, .. }) if path.ends_with("alpha")));
+        assert!(matches!(run_with(&["--all-errors"]).await, Err(ExemplifyError::Multiple { errors }) if errors.len() == 2));
+
+        // The example which could be written is still recorded
+        assert_eq!(std::fs::read_to_string(root.join("out/.exemplify-generated")).unwrap(), "beta");
+        assert!(std::fs::read_to_string(root.join("manifest.json")).unwrap().contains("\"beta\""));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
 }
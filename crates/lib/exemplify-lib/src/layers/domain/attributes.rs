@@ -0,0 +1,366 @@
+use std::iter::Peekable;
+use std::ops::Range;
+use std::str::CharIndices;
+
+/// A single `key=value` or `flag` entry of a marker attribute block
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute {
+    pub key: String,
+    pub value: AttributeValue,
+    /// Byte range of the key within the line
+    pub key_columns: Range<usize>,
+    /// Byte range of the value within the line, or of the key for flags
+    pub value_columns: Range<usize>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    Text(String),
+    Integer(u64),
+    Boolean(bool)
+}
+
+/// The parsed `{...}` block of a marker
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeBlock {
+    pub attributes: Vec<Attribute>,
+    /// Byte range of the whole block, braces included
+    pub columns: Range<usize>
+}
+
+/// Why an attribute block could not be parsed, and where
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeError {
+    pub message: String,
+    pub columns: Range<usize>
+}
+
+impl AttributeBlock {
+    pub fn get(&self, key: &str) -> Option<&Attribute> {
+        self.attributes.iter().find(|attribute| attribute.key == key)
+    }
+
+    /// Fail on the first attribute whose key is not in `known_keys`
+    pub fn check_keys(&self, known_keys: &[&str]) -> Result<(), AttributeError> {
+        match self.attributes.iter().find(|attribute| !known_keys.contains(&attribute.key.as_str())) {
+            Some(attribute) => Err(AttributeError {
+                message: format!("unknown attribute {}, expected one of {}", attribute.key, known_keys.join(", ")),
+                columns: attribute.key_columns.clone()
+            }),
+            _ => Ok(())
+        }
+    }
+
+    pub fn text(&self, key: &str) -> Result<Option<String>, AttributeError> {
+        match self.get(key) {
+            Some(Attribute { value: AttributeValue::Text(text), .. }) => Ok(Some(text.clone())),
+            Some(attribute) => Err(attribute.type_error("a quoted string")),
+            _ => Ok(None)
+        }
+    }
+
+    pub fn integer(&self, key: &str) -> Result<Option<(u64, Range<usize>)>, AttributeError> {
+        match self.get(key) {
+            Some(Attribute { value: AttributeValue::Integer(value), value_columns, .. }) => Ok(Some((*value, value_columns.clone()))),
+            Some(attribute) => Err(attribute.type_error("an integer")),
+            _ => Ok(None)
+        }
+    }
+
+    pub fn flag(&self, key: &str) -> Result<bool, AttributeError> {
+        match self.get(key) {
+            Some(Attribute { value: AttributeValue::Boolean(value), .. }) => Ok(*value),
+            Some(attribute) => Err(attribute.type_error("true, false or no value")),
+            _ => Ok(false)
+        }
+    }
+}
+
+impl Attribute {
+    fn type_error(&self, expected: &str) -> AttributeError {
+        AttributeError {
+            message: format!("{} must be {}", self.key, expected),
+            columns: self.value_columns.clone()
+        }
+    }
+}
+
+/// Parse the attribute block starting at the first `{` at or after `offset` in the line.
+/// Returns `None` if there is no block. Text after the closing brace is ignored.
+///
+/// The block holds whitespace separated entries of the forms `key="text"`, `key=123`,
+/// `key=true`, `key=false` and `flag`, the last one being short for `flag=true`.
+/// Text values support the escapes `\"`, `\\`, `\n`, `\t` and `\u{...}`
+pub fn parse_attribute_block(line: &str, offset: usize) -> Option<Result<AttributeBlock, AttributeError>> {
+    let start = offset + line[offset..].find('{')?;
+
+    Some(Parser { line, chars: line[start + 1..].char_indices().peekable(), base: start + 1 }.parse_block(start))
+}
+
+struct Parser<'a> {
+    line: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    /// Byte position of the first character given to `chars`
+    base: usize
+}
+
+impl<'a> Parser<'a> {
+    fn parse_block(mut self, start: usize) -> Result<AttributeBlock, AttributeError> {
+        let mut attributes: Vec<Attribute> = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some((position, '}')) => {
+                    return Ok(AttributeBlock { attributes, columns: start..position + 1 });
+                }
+                Some((_, character)) if is_key_start(character) => {
+                    let attribute = self.parse_attribute()?;
+
+                    if let Some(previous) = attributes.iter().find(|previous| previous.key == attribute.key) {
+                        return Err(AttributeError {
+                            message: format!("{} is given more than once, first at column {}", attribute.key, previous.key_columns.start + 1),
+                            columns: attribute.key_columns
+                        });
+                    }
+
+                    // Entries must be separated, the next one may not follow the value directly
+                    let end = attribute.value_columns.end;
+
+                    match self.line[end..].chars().next() {
+                        Some(character) if character.is_whitespace() || character == '}' => {}
+                        Some(character) => return Err(self.unexpected(end, character, "whitespace or }")),
+                        None => return Err(self.unclosed(start))
+                    }
+
+                    attributes.push(attribute);
+                }
+                Some((position, character)) => return Err(self.unexpected(position, character, "an attribute name or }")),
+                None => return Err(self.unclosed(start))
+            }
+        }
+    }
+
+    fn parse_attribute(&mut self) -> Result<Attribute, AttributeError> {
+        let key_start = self.position();
+
+        while let Some((_, character)) = self.peek() {
+            if !is_key_character(character) {
+                break;
+            }
+
+            self.chars.next();
+        }
+
+        let key_columns = key_start..self.position();
+        let key = self.line[key_columns.clone()].to_string();
+
+        self.skip_whitespace();
+
+        if !matches!(self.peek(), Some((_, '='))) {
+            return Ok(Attribute { key, value: AttributeValue::Boolean(true), value_columns: key_columns.clone(), key_columns });
+        }
+
+        self.chars.next();
+        self.skip_whitespace();
+
+        let value_start = self.position();
+        let value = match self.peek() {
+            Some((_, '"')) => AttributeValue::Text(self.parse_text()?),
+            Some((_, character)) if character.is_ascii_digit() => self.parse_integer()?,
+            Some((_, character)) if character.is_alphabetic() => self.parse_boolean()?,
+            Some((position, character)) => return Err(self.unexpected(position, character, "a value")),
+            None => return Err(AttributeError { message: format!("missing value for {}", key), columns: key_columns })
+        };
+
+        Ok(Attribute { key, value, key_columns, value_columns: value_start..self.position() })
+    }
+
+    fn parse_text(&mut self) -> Result<String, AttributeError> {
+        let (start, _) = self.chars.next().unwrap();
+        let start = self.base + start;
+        let mut text = String::new();
+
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(text),
+                Some((position, '\\')) => {
+                    let position = self.base + position;
+
+                    match self.chars.next() {
+                        Some((_, '"')) => text.push('"'),
+                        Some((_, '\\')) => text.push('\\'),
+                        Some((_, 'n')) => text.push('\n'),
+                        Some((_, 't')) => text.push('\t'),
+                        Some((_, 'u')) => text.push(self.parse_unicode_escape(position)?),
+                        Some((end, character)) => return Err(AttributeError {
+                            message: format!("unknown escape \\{}", character),
+                            columns: position..self.base + end + character.len_utf8()
+                        }),
+                        None => return Err(self.unterminated_text(start))
+                    }
+                }
+                Some((_, character)) => text.push(character),
+                None => return Err(self.unterminated_text(start))
+            }
+        }
+    }
+
+    fn parse_unicode_escape(&mut self, start: usize) -> Result<char, AttributeError> {
+        let invalid = |parser: &mut Parser| AttributeError {
+            message: "invalid unicode escape, expected \\u{...} with 1 to 6 hex digits".into(),
+            columns: start..parser.position()
+        };
+
+        if !matches!(self.chars.next(), Some((_, '{'))) {
+            return Err(invalid(self));
+        }
+
+        let mut digits = String::new();
+
+        loop {
+            match self.chars.next() {
+                Some((_, '}')) => break,
+                Some((_, character)) if character.is_ascii_hexdigit() && digits.len() < 6 => digits.push(character),
+                _ => return Err(invalid(self))
+            }
+        }
+
+        u32::from_str_radix(&digits, 16).ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| invalid(self))
+    }
+
+    fn parse_integer(&mut self) -> Result<AttributeValue, AttributeError> {
+        let start = self.position();
+
+        while let Some((_, character)) = self.peek() {
+            if !character.is_ascii_digit() {
+                break;
+            }
+
+            self.chars.next();
+        }
+
+        let columns = start..self.position();
+
+        self.line[columns.clone()].parse()
+            .map(AttributeValue::Integer)
+            .map_err(|_| AttributeError { message: "integer is too large".into(), columns })
+    }
+
+    fn parse_boolean(&mut self) -> Result<AttributeValue, AttributeError> {
+        let start = self.position();
+
+        while let Some((_, character)) = self.peek() {
+            if !character.is_alphanumeric() {
+                break;
+            }
+
+            self.chars.next();
+        }
+
+        let columns = start..self.position();
+
+        match &self.line[columns.clone()] {
+            "true" => Ok(AttributeValue::Boolean(true)),
+            "false" => Ok(AttributeValue::Boolean(false)),
+            word => Err(AttributeError {
+                message: format!("unexpected {}, text values must be quoted", word),
+                columns
+            })
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some((_, character)) = self.peek() {
+            if !character.is_whitespace() {
+                break;
+            }
+
+            self.chars.next();
+        }
+    }
+
+    fn peek(&mut self) -> Option<(usize, char)> {
+        let base = self.base;
+
+        self.chars.peek().map(|(position, character)| (base + position, *character))
+    }
+
+    /// Byte position of the next character, or the end of the line
+    fn position(&mut self) -> usize {
+        match self.peek() {
+            Some((position, _)) => position,
+            _ => self.line.len()
+        }
+    }
+
+    fn unexpected(&self, position: usize, character: char, expected: &str) -> AttributeError {
+        AttributeError {
+            message: format!("unexpected '{}', expected {}", character, expected),
+            columns: position..position + character.len_utf8()
+        }
+    }
+
+    fn unclosed(&self, start: usize) -> AttributeError {
+        AttributeError { message: "attribute block is not closed with }".into(), columns: start..self.line.len() }
+    }
+
+    fn unterminated_text(&self, start: usize) -> AttributeError {
+        AttributeError { message: "text value is missing its closing quote".into(), columns: start..self.line.len() }
+    }
+}
+
+fn is_key_start(character: char) -> bool {
+    character.is_ascii_alphabetic() || character == '_'
+}
+
+fn is_key_character(character: char) -> bool {
+    character.is_ascii_alphanumeric() || character == '_' || character == '-'
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(line: &str) -> Result<AttributeBlock, AttributeError> {
+        parse_attribute_block(line, 0).unwrap()
+    }
+
+    #[test]
+    fn test_attribute_values() {
+        let block = parse(r#"// ##start##{name="Step 2: configure \"retry\"" title = "Konfiguration für Ärzte \u{1F600}" part=12 hidden draft=false} -->"#).unwrap();
+
+        assert_eq!(block.text("name").unwrap().unwrap(), "Step 2: configure \"retry\"");
+        assert_eq!(block.text("title").unwrap().unwrap(), "Konfiguration für Ärzte \u{1F600}");
+        assert_eq!(block.integer("part").unwrap().unwrap().0, 12);
+        assert!(block.flag("hidden").unwrap());
+        assert!(!block.flag("draft").unwrap());
+        assert_eq!(&r#"// ##start##{name="x"} -->"#[parse(r#"// ##start##{name="x"} -->"#).unwrap().columns], r#"{name="x"}"#);
+        assert_eq!(parse_attribute_block("// ##start##", 0), None);
+    }
+
+    #[test]
+    fn test_attribute_errors() {
+        let error = |line: &str| {
+            let error = parse(line).unwrap_err();
+
+            (error.columns.start, error.columns.end)
+        };
+
+        assert_eq!(error(r#"{name="a" name="b"}"#), (10, 14));
+        assert_eq!(error(r#"{name="a"title="b"}"#), (9, 10));
+        assert_eq!(error(r#"{name=a}"#), (6, 7));
+        assert_eq!(error(r#"{name="a}"#), (6, 9));
+        assert_eq!(error(r#"{name="\q"}"#), (7, 9));
+        assert_eq!(error(r#"{name="a""#), (0, 9));
+        assert_eq!(error(r#"{="a"}"#), (1, 2));
+
+        let block = parse(r#"{name="a" colour="red"}"#).unwrap();
+
+        assert_eq!(block.check_keys(&["name"]).unwrap_err().columns, 10..16);
+        assert_eq!(block.integer("name").unwrap_err().columns, 6..9);
+    }
+}
@@ -1,31 +1,67 @@
+use std::collections::VecDeque;
+use std::convert::TryFrom;
 use std::io::{BufRead, BufReader, Read};
+use std::ops::Range;
 use std::pin::Pin;
-use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 
 use futures::stream::Stream;
 use futures::task::{Context, Poll};
 
+use crate::layers::domain::attributes::{AttributeError, parse_attribute_block};
 use crate::layers::domain::entities::chunk::{Chunk, ChunkLine};
-use crate::layers::domain::parser_settings::ParserSettings;
+use crate::layers::domain::error::{ExemplifyError, SourceLocation};
+use crate::layers::domain::languages::{CommentScanner, comment_syntax_for_source};
+use crate::layers::domain::parser_settings::{MarkerDialect, ParserSettings};
 use crate::layers::domain::reader_factory::ReaderContext;
 
+/// The attributes a chunk start marker may have
+const CHUNK_ATTRIBUTES: &[&str] = &["name", "part", "indentation", "title", "language", "id"];
+
 pub struct ChunkReader<Reader> {
     source_name: String,
     reader: Arc<Mutex<BufReader<Reader>>>,
     parser_settings: ParserSettings,
     current_chunk: Option<Chunk>,
     current_line: usize,
+    /// Open regions of the mdBook and Asciidoctor dialects, which may overlap each other and the current chunk
+    anchor_chunks: Vec<Chunk>,
+    /// Start line of a chunk whose start marker could not be parsed.
+    /// Its content is skipped until the matching end marker
+    broken_chunk_start: Option<usize>,
+    completed_chunks: Vec<Chunk>,
+    errors: VecDeque<ExemplifyError>,
+    /// Whether the current line is in a hidden region of the open chunk
+    hiding: bool,
+    /// Whether the current line is in an elided region of the open chunk
+    eliding: bool,
+    /// Finds the comments of the source, or `None` if markers are recognised anywhere on a line
+    comment_scanner: Option<CommentScanner>,
+    /// Comments of the current line
+    line_comments: Vec<Range<usize>>,
+    /// First line of the block comment left open by the previous lines, if any
+    open_comment_start: Option<usize>,
+    /// Indentation of the first line of the open block comment
+    open_comment_indentation: String,
+    /// Indentation of the marker on the current line, when it differs from the indentation of the line
+    marker_indentation: Option<String>,
+    /// Whether the current line continues a block comment holding a marker.
+    /// Such lines belong to the marker rather than to the chunk
+    in_marker_comment: bool,
 }
 
 impl<Reader: Read> Stream for ChunkReader<Reader> {
-    type Item = Result<Vec<Chunk>, String>;
+    type Item = Result<Vec<Chunk>, ExemplifyError>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let mut completed_chunks = Vec::new();
+        if let Some(error) = self.errors.pop_front() {
+            return Poll::Ready(Some(Err(error)));
+        }
 
         let rc = self.reader.clone();
-        let mut reader = rc.lock().map_err(|e| { e.to_string() })?;
+        let source_name = self.source_name.clone();
+        let mut reader = rc.lock()
+            .map_err(|e| ExemplifyError::io(&source_name, std::io::Error::other(e.to_string())))?;
 
         let taken_lines = reader.by_ref().lines().take(100);
         let mut read_count = 0;
@@ -35,29 +71,30 @@ impl<Reader: Read> Stream for ChunkReader<Reader> {
             self.current_line += 1;
 
             match line {
-                Err(err) => return Poll::Ready(Some(Err(err.to_string()))),
+                Err(err) => return Poll::Ready(Some(Err(ExemplifyError::io(&self.source_name, err)))),
                 Ok(line) => {
-                    if let Some(result) = self.process_line(&line, self.current_line)? {
-                        completed_chunks.push(result);
-                    }
+                    let line_number = self.current_line;
+                    self.process_line(&line, line_number);
                 }
             }
         }
 
         if read_count == 0 {
-            if self.current_chunk.is_some() {
-                if let Some(chunk) = self.finalize_chunk()? {
-                    completed_chunks.push(chunk);
-                }
-            }
+            self.finalize_chunk();
+
+            // Like mdBook, regions left open run to the end of the file
+            let anchor_chunks = std::mem::take(&mut self.anchor_chunks);
+            self.completed_chunks.extend(anchor_chunks);
         }
 
-        if completed_chunks.len() > 0 {
-            Poll::Ready(Some(Ok(completed_chunks)))
+        if let Some(error) = self.errors.pop_front() {
+            Poll::Ready(Some(Err(error)))
+        } else if !self.completed_chunks.is_empty() {
+            Poll::Ready(Some(Ok(std::mem::take(&mut self.completed_chunks))))
         } else if read_count == 0 {
             Poll::Ready(None)
         } else {
-            cx.waker().clone().wake();
+            cx.waker().wake_by_ref();
 
             Poll::Pending
         }
@@ -66,130 +103,383 @@ impl<Reader: Read> Stream for ChunkReader<Reader> {
 
 impl<Reader: Read> ChunkReader<Reader> {
     pub fn new(reader_context: ReaderContext<Reader>, parser_settings: ParserSettings) -> Self {
+        let comment_scanner = match parser_settings.markers_anywhere {
+            true => None,
+            false => comment_syntax_for_source(&reader_context.source_name, &parser_settings.extension_languages).map(CommentScanner::new)
+        };
+
         Self {
             reader: Arc::new(Mutex::new(BufReader::new(reader_context.reader))),
             parser_settings,
             current_chunk: None,
+            anchor_chunks: Vec::new(),
             source_name: reader_context.source_name,
             current_line: 0,
+            broken_chunk_start: None,
+            completed_chunks: Vec::new(),
+            errors: VecDeque::new(),
+            hiding: false,
+            eliding: false,
+            comment_scanner,
+            line_comments: Vec::new(),
+            open_comment_start: None,
+            open_comment_indentation: String::new(),
+            marker_indentation: None,
+            in_marker_comment: false,
         }
     }
 
-    fn process_line(self: &mut Pin<&mut Self>, line: &String, line_number: usize) -> Result<Option<Chunk>, String> {
-        let has_start = line.contains(&self.parser_settings.start_token);
-        let has_end = line.contains(&self.parser_settings.end_token);
+    /// Process a single line of input.
+    /// Errors are queued rather than returned, and the reader recovers so that the rest of the input can still be checked
+    fn process_line(self: &mut Pin<&mut Self>, line: &str, line_number: usize) {
+        let continued_comment_start = self.open_comment_start;
 
-        match &mut self.current_chunk {
-            Some(chunk) => {
-                if has_start {
-                    return Err(format!("Error {}[{}]: attempting to start chunk-in-chunk", self.source_name, line_number));
-                }
+        if let Some(scanner) = &mut self.comment_scanner {
+            let comments = scanner.comments(line);
+            let left_open = scanner.in_block_comment();
 
-                if has_end {
-                    return self.finalize_chunk();
-                }
+            self.line_comments = comments;
+            self.open_comment_start = match left_open {
+                true => continued_comment_start.or(Some(line_number)),
+                false => None
+            };
+
+            if left_open && continued_comment_start.is_none() {
+                self.open_comment_indentation = indentation_of(line).to_string();
+            }
+        }
+
+        self.marker_indentation = None;
+
+        if self.in_marker_comment {
+            self.in_marker_comment = self.open_comment_start.is_some();
+            return;
+        }
+
+        let anchors = self.find_anchors(line);
 
-                if !has_start && !has_end {
-                    {
-                        chunk.content.push(ChunkLine {
-                            value: line.clone(),
-                            line_number,
-                        });
-                    }
+        if !anchors.is_empty() || self.has_any_marker(line) {
+            // A marker in a block comment spanning several lines takes the whole comment with it
+            if let Some(comment_start) = continued_comment_start {
+                self.drop_lines_from(comment_start);
+                self.marker_indentation = Some(self.open_comment_indentation.clone());
+            }
+
+            self.in_marker_comment = self.open_comment_start.is_some();
+        }
+
+        if !anchors.is_empty() {
+            for anchor in anchors {
+                match anchor.end {
+                    false => self.start_anchor(anchor, line_number),
+                    true => self.end_anchor(anchor, line_number)
                 }
+            }
 
-                Ok(None)
+            return;
+        }
+
+        let exemplify_dialect = self.parser_settings.dialects.contains(&MarkerDialect::Exemplify);
+        let has_start = exemplify_dialect && self.has_token(line, &self.parser_settings.start_token);
+        let has_end = exemplify_dialect && self.has_token(line, &self.parser_settings.end_token);
+
+        let open_chunk_start = match (&self.current_chunk, self.broken_chunk_start) {
+            (Some(chunk), _) => Some(chunk.start_line),
+            (None, broken_start) => broken_start
+        };
+
+        match open_chunk_start {
+            Some(open_chunk_start) => {
+                if has_start {
+                    let error = ExemplifyError::NestedChunk {
+                        location: SourceLocation::new(&self.source_name, line_number)
+                            .with_columns(self.token_columns(line, &self.parser_settings.start_token)),
+                        open_chunk: SourceLocation::new(&self.source_name, open_chunk_start)
+                    };
+                    self.errors.push_back(error);
+
+                    // Treat the open chunk as ended, most likely its end marker is missing
+                    self.finalize_chunk();
+                    self.start_chunk(line, line_number);
+                } else if has_end {
+                    self.finalize_chunk();
+                } else {
+                    self.add_content_line(line, line_number);
+                }
             }
             None => {
                 if has_start {
-                    let params = Self::extract_chunk_params(&line, &self.source_name, line_number)?;
-
-                    self.current_chunk = Some(Chunk {
-                        example_name: params.name,
-                        content: vec![],
-                        part_number: params.part,
-                        indentation: params.indentation,
-                        source_name: self.source_name.clone(),
-                        start_line: line_number,
-                        title: params.title,
-                        language: params.language,
-                        id: params.id
-                    });
-                    return Ok(None);
+                    self.start_chunk(line, line_number);
+                } else if has_end {
+                    let error = ExemplifyError::EndOutsideChunk {
+                        location: SourceLocation::new(&self.source_name, line_number)
+                            .with_columns(self.token_columns(line, &self.parser_settings.end_token))
+                    };
+                    self.errors.push_back(error);
+                } else if !self.anchor_chunks.is_empty() {
+                    self.add_content_line(line, line_number);
                 }
+            }
+        }
+    }
 
-                if has_end {
-                    return Err(format!("Error {}[{}]: attempting to end chunk outside of chunk", self.source_name, line_number));
-                }
+    /// Open a region of the mdBook or Asciidoctor dialect
+    fn start_anchor(self: &mut Pin<&mut Self>, anchor: Anchor, line_number: usize) {
+        let location = SourceLocation::new(&self.source_name, line_number).with_columns(Some(anchor.columns.clone()));
 
-                Ok(None)
+        if let Some(open_chunk) = self.anchor_chunks.iter().find(|chunk| chunk.example_name == anchor.name) {
+            let error = ExemplifyError::NestedChunk {
+                location,
+                open_chunk: SourceLocation::new(&self.source_name, open_chunk.start_line)
+            };
+            self.errors.push_back(error);
+
+            return;
+        }
+
+        let chunk = Chunk {
+            example_name: anchor.name,
+            content: vec![],
+            part_number: None,
+            indentation: None,
+            source_name: self.source_name.clone(),
+            start_line: line_number,
+            title: None,
+            language: None,
+            id: None,
+            marker_columns: Some(anchor.columns),
+            part_columns: None,
+            anchored: true
+        };
+
+        self.anchor_chunks.push(chunk);
+    }
+
+    fn end_anchor(self: &mut Pin<&mut Self>, anchor: Anchor, line_number: usize) {
+        match self.anchor_chunks.iter().position(|chunk| chunk.example_name == anchor.name) {
+            Some(index) => {
+                let chunk = self.anchor_chunks.remove(index);
+                self.completed_chunks.push(chunk);
+                self.reset_regions();
+            }
+            _ => {
+                let error = ExemplifyError::EndOutsideChunk {
+                    location: SourceLocation::new(&self.source_name, line_number).with_columns(Some(anchor.columns))
+                };
+                self.errors.push_back(error);
             }
         }
     }
 
-    fn extract_chunk_params(line: &String, source_name: &String, line_number: usize) -> Result<ChunkParams, String> {
+    /// The mdBook and Asciidoctor markers on the line, in order
+    fn find_anchors(&self, line: &str) -> Vec<Anchor> {
         lazy_static::lazy_static! {
-            static ref VAL_RE: regex::Regex = regex::Regex::new("(([a-zA-Z]+)\\s?=\\s?\"([a-zA-Z\\s0-9\\-\\(\\)_\\+\\.,'`@\\[\\]\\?!/]+)\")|(([a-zA-Z]+)\\s?=\\s?([0-9]+))").unwrap();
+            static ref MDBOOK_ANCHOR_RE: regex::Regex = regex::Regex::new("\\bANCHOR(_END)?:\\s*([\\w-]+)").unwrap();
+            static ref ASCIIDOC_TAG_RE: regex::Regex = regex::Regex::new("\\b(tag|end)::([^\\s\\[]+)\\[\\]").unwrap();
         }
 
-        let mut name: String = "".into();
-        let mut part = None;
-        let mut indentation = None;
-        let mut title = None;
-        let mut language = None;
-        let mut id = None;
+        let mut anchors: Vec<Anchor> = self.parser_settings.dialects.iter()
+            .flat_map(|dialect| {
+                let (anchor_re, end_marker): (&regex::Regex, fn(&regex::Captures) -> bool) = match dialect {
+                    MarkerDialect::MdbookAnchor => (&MDBOOK_ANCHOR_RE, |captures| captures.get(1).is_some()),
+                    MarkerDialect::AsciidocTag => (&ASCIIDOC_TAG_RE, |captures| &captures[1] == "end"),
+                    MarkerDialect::Exemplify => return vec![]
+                };
+
+                anchor_re.captures_iter(line)
+                    .map(|captures| {
+                        let marker = captures.get(0).unwrap();
+
+                        Anchor {
+                            name: captures[2].to_string(),
+                            end: end_marker(&captures),
+                            columns: marker.start()..marker.end()
+                        }
+                    })
+                    .filter(|anchor| self.in_comment(&anchor.columns))
+                    .collect()
+            })
+            .collect();
+
+        anchors.sort_by_key(|anchor| anchor.columns.start);
+        anchors
+    }
 
-        for val in VAL_RE.captures_iter(line) {
-            let param_name_name = val.get(2);
-            let param_name_val = val.get(3);
+    /// Add a line to the open chunk, unless it is hidden or elided
+    fn add_content_line(self: &mut Pin<&mut Self>, line: &str, line_number: usize) {
+        let indentation = self.marker_indentation.clone().unwrap_or_else(|| indentation_of(line).to_string());
+        let indentation = indentation.as_str();
+
+        if self.has_token(line, &self.parser_settings.hide_start_token) {
+            self.hiding = true;
+        } else if self.has_token(line, &self.parser_settings.hide_end_token) {
+            self.hiding = false;
+        } else if self.has_token(line, &self.parser_settings.elide_start_token) {
+            if !self.hiding && !self.eliding {
+                self.push_line(indentation, line_number, true);
+            }
 
-            let param_part_name = val.get(5);
-            let param_part_val = val.get(6);
+            self.eliding = true;
+        } else if self.has_token(line, &self.parser_settings.elide_end_token) {
+            self.eliding = false;
+        } else if self.hiding || self.eliding || self.has_token(line, &self.parser_settings.hide_token) {
+            // Left out of the example
+        } else if self.has_token(line, &self.parser_settings.elide_token) {
+            self.push_line(indentation, line_number, true);
+        } else {
+            self.push_line(line, line_number, false);
+        }
+    }
 
+    fn push_line(self: &mut Pin<&mut Self>, value: &str, line_number: usize, elided: bool) {
+        let this = &mut **self;
 
-            if let Some(pname) = param_name_name {
-                if let Some(n) = param_name_val {
-                    let val = n.as_str().to_string().clone();
+        for chunk in this.current_chunk.iter_mut().chain(this.anchor_chunks.iter_mut()) {
+            chunk.content.push(ChunkLine {
+                value: value.to_string(),
+                line_number,
+                elided
+            });
+        }
+    }
 
-                    match pname.as_str().to_string().trim() {
-                        "name" => name = val,
-                        "title" => title = Some(val),
-                        "language" => language = Some(val),
-                        "id" => id = Some(val),
-                        _ => {}
-                    }
-                }
+    fn start_chunk(self: &mut Pin<&mut Self>, line: &str, line_number: usize) {
+        match self.extract_chunk_params(line, line_number) {
+            Ok(params) => {
+                self.current_chunk = Some(Chunk {
+                    example_name: params.name,
+                    content: vec![],
+                    part_number: params.part,
+                    indentation: params.indentation,
+                    source_name: self.source_name.clone(),
+                    start_line: line_number,
+                    title: params.title,
+                    language: params.language,
+                    id: params.id,
+                    marker_columns: params.marker_columns,
+                    part_columns: params.part_columns,
+                    anchored: false
+                });
             }
-
-            if let Some(pname) = param_part_name {
-                if let Some(part_val) = param_part_val {
-                    match pname.as_str().to_string().trim() {
-                        "part" => part = Some(u32::from_str(part_val.as_str()).map_err(|_| format!("{}[{}]: Failed to parse part number {}", source_name, line_number, part_val.as_str().to_string()))?),
-                        "indentation" => indentation = Some(u32::from_str(part_val.as_str())
-                            .map_err(|_| format!("{}[{}]: Failed to parse indentation number {}", source_name, line_number, part_val.as_str().to_string()))?),
-                        _ => {}
-                    }
-                }
+            Err(error) => {
+                self.errors.push_back(error);
+                self.broken_chunk_start = Some(line_number);
             }
         }
+    }
 
-        if name.len() == 0 {
-            return Err(format!("{}[{}]: Missing name", source_name, line_number));
-        }
+    fn extract_chunk_params(&self, line: &str, line_number: usize) -> Result<ChunkParams, ExemplifyError> {
+        let location = SourceLocation::new(&self.source_name, line_number);
+        let invalid = |error: AttributeError| ExemplifyError::InvalidAttributes {
+            location: location.clone().with_columns(Some(error.columns)),
+            message: error.message
+        };
+
+        let token_end = self.find_token(line, &self.parser_settings.start_token).unwrap_or(0) + self.parser_settings.start_token.len();
+
+        let block = match parse_attribute_block(line, token_end) {
+            Some(block) => block.map_err(invalid)?,
+            _ => return Err(ExemplifyError::MissingName { location: location.with_columns(self.token_columns(line, &self.parser_settings.start_token)) })
+        };
+
+        block.check_keys(CHUNK_ATTRIBUTES).map_err(invalid)?;
+
+        let name = match block.text("name").map_err(invalid)? {
+            Some(name) if !name.is_empty() => name,
+            _ => return Err(ExemplifyError::MissingName { location: location.with_columns(Some(block.columns)) })
+        };
+
+        let part = block.integer("part").map_err(invalid)?;
+        let part_columns = part.as_ref().map(|_| {
+            let attribute = block.get("part").unwrap();
+
+            attribute.key_columns.start..attribute.value_columns.end
+        });
 
         Ok(ChunkParams {
-            part,
+            part: part.map(|(value, columns)| integer_attribute("part", value, columns, &location)).transpose()?,
+            indentation: block.integer("indentation").map_err(invalid)?
+                .map(|(value, columns)| integer_attribute("indentation", value, columns, &location))
+                .transpose()?,
+            title: block.text("title").map_err(invalid)?,
+            language: block.text("language").map_err(invalid)?,
+            id: block.text("id").map_err(invalid)?,
             name,
-            indentation,
-            title,
-            language,
-            id
+            marker_columns: Some(block.columns),
+            part_columns
         })
     }
 
-    fn finalize_chunk(self: &mut Pin<&mut Self>) -> Result<Option<Chunk>, String> {
-        Ok(self.current_chunk.take())
+    /// Position of the first occurrence of the token in a comment of the line,
+    /// or anywhere on the line if the comment syntax of the source is unknown
+    fn find_token(&self, line: &str, token: &str) -> Option<usize> {
+        line.match_indices(token)
+            .map(|(position, _)| position)
+            .find(|position| self.in_comment(&(*position..position + token.len())))
+    }
+
+    /// Whether the range of the current line is inside a comment, or markers may be anywhere
+    fn in_comment(&self, columns: &Range<usize>) -> bool {
+        match &self.comment_scanner {
+            Some(_) => self.line_comments.iter().any(|comment| comment.start <= columns.start && columns.end <= comment.end),
+            _ => true
+        }
+    }
+
+    fn has_any_marker(&self, line: &str) -> bool {
+        let settings = &self.parser_settings;
+        let exemplify_dialect = settings.dialects.contains(&MarkerDialect::Exemplify);
+
+        if exemplify_dialect && (self.has_token(line, &settings.start_token) || self.has_token(line, &settings.end_token)) {
+            return true;
+        }
+
+        [&settings.hide_start_token, &settings.hide_end_token, &settings.hide_token,
+            &settings.elide_start_token, &settings.elide_end_token, &settings.elide_token]
+            .iter()
+            .any(|token| self.has_token(line, token))
+    }
+
+    /// Remove the lines of the open chunk starting at the given line, when they turn out to belong to a marker
+    fn drop_lines_from(self: &mut Pin<&mut Self>, line_number: usize) {
+        let this = &mut **self;
+
+        for chunk in this.current_chunk.iter_mut().chain(this.anchor_chunks.iter_mut()) {
+            chunk.content.retain(|line| line.line_number < line_number);
+        }
+    }
+
+    fn has_token(&self, line: &str, token: &str) -> bool {
+        self.find_token(line, token).is_some()
+    }
+
+    fn token_columns(&self, line: &str, token: &str) -> Option<Range<usize>> {
+        self.find_token(line, token).map(|start| start..start + token.len())
+    }
+
+    fn finalize_chunk(self: &mut Pin<&mut Self>) {
+        if let Some(chunk) = self.current_chunk.take() {
+            self.completed_chunks.push(chunk);
+        }
+
+        self.broken_chunk_start = None;
+        self.reset_regions();
     }
+
+    /// End the hidden and elided regions once no chunk is open any more
+    fn reset_regions(self: &mut Pin<&mut Self>) {
+        if self.current_chunk.is_none() && self.anchor_chunks.is_empty() {
+            self.hiding = false;
+            self.eliding = false;
+        }
+    }
+}
+
+/// A start or end marker of the mdBook or Asciidoctor dialect
+struct Anchor {
+    name: String,
+    end: bool,
+    columns: Range<usize>
 }
 
 struct ChunkParams {
@@ -198,5 +488,19 @@ struct ChunkParams {
     indentation: Option<u32>,
     title: Option<String>,
     language: Option<String>,
-    id: Option<String>
+    id: Option<String>,
+    marker_columns: Option<Range<usize>>,
+    part_columns: Option<Range<usize>>
+}
+
+fn integer_attribute(attribute: &str, value: u64, columns: Range<usize>, location: &SourceLocation) -> Result<u32, ExemplifyError> {
+    u32::try_from(value).map_err(|_| ExemplifyError::InvalidIntegerAttribute {
+        location: location.clone().with_columns(Some(columns)),
+        attribute: attribute.to_string(),
+        value: value.to_string()
+    })
+}
+
+fn indentation_of(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
 }
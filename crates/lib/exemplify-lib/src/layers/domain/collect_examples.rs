@@ -1,62 +1,162 @@
-use std::cmp::{min, Ordering};
-use std::collections::{HashMap, HashSet};
+use std::cmp::min;
+use std::collections::{BTreeMap, HashMap};
 use std::io::Read;
+use std::path::Path;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{Receiver, SyncSender};
+use std::thread::JoinHandle;
 
 use futures::{Stream, StreamExt};
 
 use crate::layers::domain::entities::chunk::Chunk;
+use crate::layers::domain::entities::chunk_cache::ChunkCache;
 use crate::layers::domain::chunk_reader::ChunkReader;
+use crate::layers::domain::languages::{elision_comment, language_for_source};
 use crate::layers::domain::parser_settings::ParserSettings;
 use crate::layers::domain::reader_factory::ReaderContext;
-use crate::layers::domain::entities::example::Example;
+use crate::layers::domain::entities::example::{Example, ExampleSource};
+use crate::layers::domain::error::{ExemplifyError, SourceLocation};
 
 
 /// Transform a stream of file readers into a stream of examples
 /// Note: this will exhaust all readers before starting the stream of examples
-pub async fn collect_examples<Reader: Read>(mut reader_factory: Pin<Box<dyn Stream<Item=Result<ReaderContext<Reader>, String>>>>, parser_settings: ParserSettings)
-                                            -> Result<Pin<Box<dyn Stream<Item=Example>>>, String> {
-    let mut chunk_cache: HashMap<String, Vec<Chunk>> = Default::default();
+///
+/// The readers are read and chunked concurrently on a pool of threads as the reader stream yields them,
+/// while the results are merged in the order of the reader stream. No particular async runtime is needed.
+///
+/// Unless `collect_all_errors` is set in the parser settings, the first error encountered is returned.
+/// Otherwise all errors are returned together as `ExemplifyError::Multiple`
+pub async fn collect_examples<Reader: Read + Send + 'static>(reader_factory: Pin<Box<dyn Stream<Item=Result<ReaderContext<Reader>, ExemplifyError>>>>, parser_settings: ParserSettings)
+                                            -> Result<Pin<Box<dyn Stream<Item=Example>>>, ExemplifyError> {
+    let mut file_cache = ChunkCache::new(&parser_settings);
+
+    collect_examples_with_cache(reader_factory, parser_settings, &mut file_cache).await
+}
+
+/// Like `collect_examples`, but the chunks of files found unchanged in `file_cache` are reused instead of parsed again.
+/// Afterwards the cache holds the chunks of every file read without errors
+pub async fn collect_examples_with_cache<Reader: Read + Send + 'static>(mut reader_factory: Pin<Box<dyn Stream<Item=Result<ReaderContext<Reader>, ExemplifyError>>>>,
+                                                       parser_settings: ParserSettings,
+                                                       file_cache: &mut ChunkCache)
+                                                       -> Result<Pin<Box<dyn Stream<Item=Example>>>, ExemplifyError> {
+    let mut chunk_cache: BTreeMap<String, Vec<Chunk>> = Default::default();
+    let mut errors = ErrorCollector::new(parser_settings.collect_all_errors);
+    let mut next_file_cache = ChunkCache::new(&parser_settings);
+
+    let pool = ParserPool::new(&parser_settings, std::mem::take(file_cache));
+    let mut source_count = 0;
 
     while let Some(reader_context) = reader_factory.next().await {
-        let reader_context = reader_context?;
+        match reader_context {
+            Ok(reader_context) => {
+                pool.parse(source_count, reader_context);
+                source_count += 1;
+            }
+            Err(error) => errors.record(error)?
+        }
+    }
+
+    for parsed_source in pool.finish()? {
+        if parsed_source.errors.is_empty() {
+            next_file_cache.insert(parsed_source.source_name, parsed_source.hash, parsed_source.chunks.clone());
+        }
+
+        for error in parsed_source.errors {
+            errors.record(error)?;
+        }
+
+        add_chunks(&mut chunk_cache, parsed_source.chunks);
+    }
+
+    *file_cache = next_file_cache;
 
-        let chunk_reader = ChunkReader::new(reader_context, parser_settings.clone());
+    let examples = finalize_examples(chunk_cache, &parser_settings, &mut errors)?;
 
-        chunk_cache = exhaust_reader(chunk_reader, chunk_cache).await?;
+    errors.finish()?;
+
+    Ok(Box::pin(futures::stream::iter(examples)))
+}
+
+/// Either fails on the first recorded error, or keeps them all for reporting at the end of the run
+struct ErrorCollector {
+    collect_all: bool,
+    errors: Vec<ExemplifyError>
+}
+
+impl ErrorCollector {
+    fn new(collect_all: bool) -> Self {
+        ErrorCollector {
+            collect_all,
+            errors: Vec::new()
+        }
     }
 
-    let examples = finalize_examples(chunk_cache)?;
+    fn record(&mut self, error: ExemplifyError) -> Result<(), ExemplifyError> {
+        if !self.collect_all {
+            return Err(error);
+        }
 
-    Ok(Box::pin(futures::stream::iter(examples.into_iter())))
+        self.errors.push(error);
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), ExemplifyError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ExemplifyError::Multiple { errors: self.errors })
+        }
+    }
 }
 
-fn finalize_examples(chunk_cache: HashMap<String, Vec<Chunk>>) -> Result<Vec<Example>, String> {
+/// Assemble the examples from their chunks. The examples are sorted by name,
+/// and their chunks by part number, source name and line.
+/// Examples without a language attribute get the language of their first source file
+fn finalize_examples(chunk_cache: BTreeMap<String, Vec<Chunk>>, parser_settings: &ParserSettings, errors: &mut ErrorCollector) -> Result<Vec<Example>, ExemplifyError> {
     let mut examples = Vec::new();
 
     for v in &chunk_cache {
-        verify_example(&v.1)?;
-
         let mut chunks: Vec<Chunk> = v.1[..].to_vec();
 
-        chunks.sort_by(|lhs, rhs| {
-            if let Some(r) = rhs.part_number {
-                if let Some(l) = lhs.part_number {
-                    if l < r {
-                        return Ordering::Less;
-                    } else {
-                        return Ordering::Greater;
-                    }
-                }
-            }
+        number_anchored_chunks(&mut chunks);
 
-            return Ordering::Equal;
+        for error in verify_example(&chunks) {
+            errors.record(error)?;
+        }
+
+        chunks.sort_by(|lhs, rhs| {
+            lhs.part_number.cmp(&rhs.part_number)
+                .then_with(|| lhs.source_name.cmp(&rhs.source_name))
+                .then_with(|| lhs.start_line.cmp(&rhs.start_line))
         });
 
         let mut example_title = None;
-        let mut example_language = None;
         let mut example_id = None;
 
+        // Needed up front, as it decides how elided code is shown
+        let example_language = chunks.iter()
+            .find_map(|chunk| chunk.language.clone())
+            .or_else(|| chunks.first().and_then(|chunk| language_for_source(&chunk.source_name, &parser_settings.extension_languages)));
+
+        let sources = chunks.iter()
+            .map(|chunk| ExampleSource {
+                source_name: chunk.source_name.clone(),
+                start_line: chunk.start_line,
+                part_number: chunk.part_number
+            })
+            .collect();
+
+        let line_locations = chunks.iter()
+            .flat_map(|chunk| chunk.content.iter().map(move |line| {
+                // The columns past the indentation, which is all that aligning the content changes
+                let indentation = line.value.len() - line.value.trim_start().len();
+
+                SourceLocation::new(&chunk.source_name, line.line_number).with_columns(Some(indentation..line.value.len()))
+            }))
+            .collect();
+
         let content = chunks.into_iter().flat_map(|v| {
             if let Some(title) = v.title {
                 if example_title.is_none() {
@@ -64,12 +164,6 @@ fn finalize_examples(chunk_cache: HashMap<String, Vec<Chunk>>) -> Result<Vec<Exa
                 }
             }
 
-            if let Some(language) = v.language {
-                if example_language.is_none() {
-                    example_language = Some(language)
-                }
-            }
-
             if let Some(id) = v.id {
                 if example_id.is_none() {
                     example_id = Some(id)
@@ -77,7 +171,15 @@ fn finalize_examples(chunk_cache: HashMap<String, Vec<Chunk>>) -> Result<Vec<Exa
             }
 
 
-            let content = v.content.into_iter().map(|l| l.value).collect();
+            let source_extension = Path::new(&v.source_name).extension().map(|extension| extension.to_string_lossy().to_string());
+            let elision = elision_comment(example_language.as_deref().or(source_extension.as_deref()));
+
+            let content = v.content.into_iter()
+                .map(|l| match l.elided {
+                    true => format!("{}{}", l.value, elision),
+                    false => l.value
+                })
+                .collect();
 
             match v.indentation {
                 Some(indentation) => indent(left_align(content), indentation),
@@ -85,7 +187,8 @@ fn finalize_examples(chunk_cache: HashMap<String, Vec<Chunk>>) -> Result<Vec<Exa
             }
         }).collect();
 
-        let example = Example::new(v.0.clone(), content, example_title, example_language, example_id);
+        let example = Example::new(v.0.clone(), content, example_title, example_language, example_id, sources)
+            .with_line_locations(line_locations);
 
         examples.push(example)
     }
@@ -93,13 +196,24 @@ fn finalize_examples(chunk_cache: HashMap<String, Vec<Chunk>>) -> Result<Vec<Exa
     Ok(examples)
 }
 
+/// Number the mdBook and Asciidoctor regions of an example by source name and line, across all sources of the run
+fn number_anchored_chunks(chunks: &mut [Chunk]) {
+    let mut anchored: Vec<&mut Chunk> = chunks.iter_mut().filter(|chunk| chunk.anchored).collect();
+
+    anchored.sort_by(|lhs, rhs| lhs.source_name.cmp(&rhs.source_name).then_with(|| lhs.start_line.cmp(&rhs.start_line)));
+
+    for (occurrence, chunk) in anchored.into_iter().enumerate() {
+        chunk.part_number = Some(occurrence as u32 + 1);
+    }
+}
+
 /// Align the string of the content vector so that the least indented
 /// line has indentation 0
 pub fn left_align(content: Vec<String>) -> Vec<String> {
     let mut min_indent = usize::MAX;
 
     for line in &content {
-        if line.len() == 0 {
+        if line.is_empty() {
             continue;
         }
 
@@ -127,43 +241,156 @@ fn indent(content: Vec<String>, indentation: u32) -> Vec<String> {
     content.into_iter().map(|line| format!("{}{}", (0..indentation).map(|_| " ").collect::<String>(), line)).collect()
 }
 
-async fn exhaust_reader<Reader: Read>(mut chunk_reader: ChunkReader<Reader>, mut chunk_cache: HashMap<String, Vec<Chunk>>) -> Result<HashMap<String, Vec<Chunk>>, String> {
-    while let Some(chunks) = chunk_reader.next().await {
-        let chunks = chunks?;
+/// The chunks of a single source, along with the errors found in it
+struct ParsedSource {
+    source_name: String,
+    hash: u64,
+    chunks: Vec<Chunk>,
+    errors: Vec<ExemplifyError>
+}
 
-        for chunk in chunks {
-            let chunk_name = chunk.example_name.clone();
+/// Reads and chunks sources on a pool of threads, one per available core.
+/// The queue of sources waiting for a worker is bounded, so only a few sources are open or held in memory at a time
+struct ParserPool<Reader: Read> {
+    queue: SyncSender<(usize, ReaderContext<Reader>)>,
+    results: Receiver<(usize, ParsedSource)>,
+    workers: Vec<JoinHandle<()>>
+}
 
-            let cache = match chunk_cache.remove(chunk.example_name.as_str()) {
-                Some(mut cache) => {
-                    cache.push(chunk);
-                    cache
-                }
-                _ => vec![chunk]
-            };
+impl<Reader: Read + Send + 'static> ParserPool<Reader> {
+    fn new(parser_settings: &ParserSettings, file_cache: ChunkCache) -> Self {
+        let concurrency = std::thread::available_parallelism().map(|count| count.get()).unwrap_or(1);
+        let (queue, queued) = std::sync::mpsc::sync_channel(concurrency * 2);
+        let (result_sender, results) = std::sync::mpsc::channel();
+        let queued = Arc::new(Mutex::new(queued));
+        let file_cache = Arc::new(file_cache);
+
+        let workers = (0..concurrency)
+            .map(|_| {
+                let queued = queued.clone();
+                let result_sender = result_sender.clone();
+                let parser_settings = parser_settings.clone();
+                let file_cache = file_cache.clone();
+
+                std::thread::spawn(move || loop {
+                    // The lock is released as soon as a source is taken, so the other workers can take the next one
+                    let next_source = match queued.lock() {
+                        Ok(queued) => queued.recv(),
+                        _ => break
+                    };
+
+                    match next_source {
+                        // The results are only dropped once the run has failed, in which case they are not needed
+                        Ok((index, reader_context)) => drop(result_sender.send((index, parse_source(reader_context, &parser_settings, &file_cache)))),
+                        _ => break
+                    }
+                })
+            })
+            .collect();
 
-            chunk_cache.insert(chunk_name, cache);
+        ParserPool { queue, results, workers }
+    }
+
+    /// Queue a source for the workers, waiting for room in the queue
+    fn parse(&self, index: usize, reader_context: ReaderContext<Reader>) {
+        // Fails only if every worker has panicked, which is reported by `finish`
+        let _ = self.queue.send((index, reader_context));
+    }
+
+    /// Wait for the workers to finish the queued sources. The results are in the order of their indices
+    fn finish(self) -> Result<Vec<ParsedSource>, ExemplifyError> {
+        drop(self.queue);
+
+        // Every worker is joined before reporting a panic, so none is left running
+        let worker_results: Vec<_> = self.workers.into_iter().map(|worker| worker.join()).collect();
+
+        for worker_result in worker_results {
+            worker_result.map_err(worker_error)?;
+        }
+
+        let mut parsed_sources: Vec<_> = self.results.into_iter().collect();
+
+        parsed_sources.sort_by_key(|(index, _)| *index);
+
+        Ok(parsed_sources.into_iter().map(|(_, parsed_source)| parsed_source).collect())
+    }
+}
+
+fn worker_error(panic: Box<dyn std::any::Any + Send>) -> ExemplifyError {
+    let message = match (panic.downcast_ref::<&str>(), panic.downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (_, Some(message)) => message.clone(),
+        _ => "a worker thread panicked".into()
+    };
+
+    ExemplifyError::Worker { message }
+}
+
+/// Read and chunk a single source, unless its content is found in the cache
+fn parse_source<Reader: Read>(mut reader_context: ReaderContext<Reader>, parser_settings: &ParserSettings, file_cache: &ChunkCache) -> ParsedSource {
+    let source_name = reader_context.source_name;
+    let mut content = Vec::new();
+
+    if let Err(error) = reader_context.reader.read_to_end(&mut content) {
+        return ParsedSource { hash: 0, chunks: vec![], errors: vec![ExemplifyError::io(&source_name, error)], source_name };
+    }
+
+    let hash = ChunkCache::content_hash(&content);
+
+    if let Some(chunks) = file_cache.get(&source_name, hash) {
+        return ParsedSource { source_name, hash, chunks: chunks.clone(), errors: vec![] };
+    }
+
+    let chunk_reader = ChunkReader::new(ReaderContext { source_name: source_name.clone(), reader: content.as_slice() }, parser_settings.clone());
+    let (chunks, errors) = futures::executor::block_on(exhaust_reader(chunk_reader));
+
+    ParsedSource { source_name, hash, chunks, errors }
+}
+
+/// Read all chunks of a single source, along with the errors found in it
+async fn exhaust_reader<Reader: Read>(mut chunk_reader: ChunkReader<Reader>) -> (Vec<Chunk>, Vec<ExemplifyError>) {
+    let mut chunks = Vec::new();
+    let mut errors = Vec::new();
+
+    while let Some(result) = chunk_reader.next().await {
+        match result {
+            Ok(mut read_chunks) => chunks.append(&mut read_chunks),
+            Err(error) => errors.push(error)
         }
     }
 
-    Ok(chunk_cache)
+    (chunks, errors)
 }
 
-fn verify_example(chunks: &Vec<Chunk>) -> Result<(), String> {
-    let mut part_set = HashSet::new();
+fn add_chunks(chunk_cache: &mut BTreeMap<String, Vec<Chunk>>, chunks: Vec<Chunk>) {
+    for chunk in chunks {
+        chunk_cache.entry(chunk.example_name.clone()).or_default().push(chunk);
+    }
+}
+
+fn verify_example(chunks: &Vec<Chunk>) -> Vec<ExemplifyError> {
+    let mut part_map: HashMap<u32, &Chunk> = HashMap::new();
+    let mut errors = Vec::new();
 
     for chunk in chunks {
         if let Some(part) = chunk.part_number {
-            if part_set.contains(&part) {
-                return Err(format!("{}[{}]: Duplicate part {} ", chunk.source_name, chunk.start_line, part).into());
+            if let Some(previous) = part_map.get(&part) {
+                errors.push(ExemplifyError::DuplicatePart {
+                    location: SourceLocation::new(&chunk.source_name, chunk.start_line).with_columns(chunk.part_columns.clone()),
+                    part,
+                    previous: SourceLocation::new(&previous.source_name, previous.start_line).with_columns(previous.part_columns.clone())
+                });
+            } else {
+                part_map.insert(part, chunk);
             }
-            part_set.insert(part);
         } else if chunks.len() > 1 {
-            return Err(format!("{}[{}]: You must provide a part number for chunks in examples with more than one chunk", chunk.source_name, chunk.start_line));
+            errors.push(ExemplifyError::MissingPartNumber {
+                location: SourceLocation::new(&chunk.source_name, chunk.start_line).with_columns(chunk.marker_columns.clone())
+            });
         }
     }
 
-    Ok(())
+    errors
 }
 
 
@@ -175,23 +402,35 @@ impl Example {
 
 #[cfg(test)]
 mod test {
+    use std::io::Cursor;
+
     use stringreader::StringReader;
 
     use crate::layers::domain::reader_factory::ReaderFactory;
     use crate::layers::domain::reader_stream::reader_stream;
+    use crate::layers::domain::parser_settings::MarkerDialect;
 
     use super::*;
 
     struct StringReaderFactory {}
 
     impl ReaderFactory<StringReader<'static>> for StringReaderFactory {
-        fn make_reader(&self, name: String) -> Result<ReaderContext<StringReader<'static>>, String> {
+        fn make_reader(&self, name: String) -> Result<ReaderContext<StringReader<'static>>, ExemplifyError> {
             let content = match name.as_str() {
                 "a" => CONTENT_A,
                 "b" => CONTENT_B,
                 "c" => CONTENT_C,
                 "d" => CONTENT_FAIL_D,
                 "e" => CONTENT_FAIL_E,
+                "f" => CONTENT_FAIL_F,
+                "hide" => CONTENT_HIDE,
+                "elide.py" => CONTENT_ELIDE,
+                "strings.rs" => CONTENT_STRINGS,
+                "page.html" => CONTENT_HTML,
+                "anchors.rs" => CONTENT_ANCHORS,
+                "more_anchors.rs" => CONTENT_MORE_ANCHORS,
+                "a-parts" => CONTENT_A_PARTS,
+                "z-parts" => CONTENT_Z_PARTS,
                 _ => panic!()
             };
 
@@ -214,7 +453,7 @@ mod test {
 
     #[tokio::test]
     async fn test_example_producer() {
-        let parser_settings = ParserSettings { start_token: "##exemplify-start##".into(), end_token: "##exemplify-end##".into() };
+        let parser_settings = ParserSettings::default();
 
         let file_name_stream = Box::pin(futures::stream::iter(
             vec![
@@ -224,7 +463,10 @@ mod test {
             ].into_iter()));
 
         let file_reader_factory = reader_stream(Box::new(StringReaderFactory {}), file_name_stream);
-        let _result = collect_examples(file_reader_factory, parser_settings.clone()).await.unwrap();
+        let result = collect_examples(file_reader_factory, parser_settings.clone()).await.unwrap();
+        let names: Vec<String> = result.map(|example| example.name).collect().await;
+
+        assert_eq!(names, vec!["example-1", "example-2", "example-3", "example-4"]);
 
         let file_name_stream = Box::pin(futures::stream::iter(
             vec![
@@ -234,7 +476,7 @@ mod test {
         let file_reader_factory = reader_stream(Box::new(StringReaderFactory {}), file_name_stream);
         let result = collect_examples(file_reader_factory, parser_settings.clone()).await;
 
-        assert_eq!(result.is_err(), true);
+        assert!(matches!(result, Err(ExemplifyError::MissingPartNumber { .. })));
 
         let file_name_stream = Box::pin(futures::stream::iter(
             vec![
@@ -244,7 +486,221 @@ mod test {
         let file_reader_factory = reader_stream(Box::new(StringReaderFactory {}), file_name_stream);
         let result = collect_examples(file_reader_factory, parser_settings.clone()).await;
 
-        assert_eq!(result.is_err(), true);
+        assert!(matches!(result, Err(ExemplifyError::NestedChunk { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_hidden_lines() {
+        let file_name_stream = Box::pin(futures::stream::iter(vec![Ok("hide".into())].into_iter()));
+        let file_reader_factory = reader_stream(Box::new(StringReaderFactory {}), file_name_stream);
+
+        let mut result = collect_examples(file_reader_factory, ParserSettings::default()).await.unwrap();
+        let example = result.next().await.unwrap();
+
+        assert_eq!(example.content, vec!["fn main() {", "    run();", "}"]);
+    }
+
+    #[tokio::test]
+    async fn test_elided_lines() {
+        let file_name_stream = Box::pin(futures::stream::iter(vec![Ok("elide.py".into())].into_iter()));
+        let file_reader_factory = reader_stream(Box::new(StringReaderFactory {}), file_name_stream);
+
+        let mut result = collect_examples(file_reader_factory, ParserSettings::default()).await.unwrap();
+        let example = result.next().await.unwrap();
+
+        assert_eq!(example.language.as_deref(), Some("python"));
+        assert_eq!(example.content, vec!["def main():", "    # ...", "    run()", "    # ..."]);
+    }
+
+    #[tokio::test]
+    async fn test_markers_only_in_comments() {
+        let collect = |parser_settings: ParserSettings| async move {
+            let file_name_stream = Box::pin(futures::stream::iter(vec![Ok("strings.rs".into())].into_iter()));
+            let file_reader_factory = reader_stream(Box::new(StringReaderFactory {}), file_name_stream);
+
+            collect_examples(file_reader_factory, parser_settings).await
+        };
+
+        let mut examples = collect(ParserSettings::default()).await.unwrap();
+
+        assert_eq!(examples.next().await.unwrap().content, vec!["const END: &str = \"##exemplify-end##\";", "let end = END;"]);
+
+        // The token in the string ends the chunk, leaving the real end marker outside of any chunk
+        assert!(matches!(collect(ParserSettings { markers_anywhere: true, ..Default::default() }).await, Err(ExemplifyError::EndOutsideChunk { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_markers_in_block_comments() {
+        let file_name_stream = Box::pin(futures::stream::iter(vec![Ok("page.html".into())].into_iter()));
+        let file_reader_factory = reader_stream(Box::new(StringReaderFactory {}), file_name_stream);
+
+        let mut result = collect_examples(file_reader_factory, ParserSettings::default()).await.unwrap();
+
+        assert_eq!(result.next().await.unwrap().content, vec!["<ul>", "  <!-- ... -->", "</ul>"]);
+    }
+
+    #[tokio::test]
+    async fn test_overlapping_anchor_dialects() {
+        let parser_settings = ParserSettings {
+            dialects: vec![MarkerDialect::MdbookAnchor, MarkerDialect::AsciidocTag],
+            ..Default::default()
+        };
+
+        let file_name_stream = Box::pin(futures::stream::iter(vec![Ok("anchors.rs".into())].into_iter()));
+        let file_reader_factory = reader_stream(Box::new(StringReaderFactory {}), file_name_stream);
+
+        let examples: Vec<Example> = collect_examples(file_reader_factory, parser_settings).await.unwrap().collect().await;
+        let content = |name: &str| examples.iter().find(|example| example.name == name).unwrap().content.clone();
+
+        assert_eq!(examples.len(), 3);
+        assert_eq!(content("all"), vec!["use std::io;", "fn main() {", "    run();", "}", "fn run() {}"]);
+        assert_eq!(content("main"), vec!["fn main() {", "    run();", "}"]);
+        assert_eq!(content("body"), vec!["    run();", "fn run() {}"]);
+        assert_eq!(examples[0].language.as_deref(), Some("rust"));
+    }
+
+    #[tokio::test]
+    async fn test_parts_across_sources() {
+        let file_name_stream = Box::pin(futures::stream::iter(vec![Ok("a-parts".into()), Ok("z-parts".into())].into_iter()));
+        let file_reader_factory = reader_stream(Box::new(StringReaderFactory {}), file_name_stream);
+
+        let mut result = collect_examples(file_reader_factory, ParserSettings::default()).await.unwrap();
+        let example = result.next().await.unwrap();
+
+        // The part number decides the order, before the source name and the line
+        assert_eq!(example.content, vec!["first", "second", "third", "fourth"]);
+        assert_eq!(example.sources.iter().map(|source| (source.source_name.as_str(), source.start_line, source.part_number)).collect::<Vec<_>>(),
+                   vec![("z-parts", 1, Some(1)), ("a-parts", 4, Some(2)), ("z-parts", 4, Some(3)), ("a-parts", 1, Some(4))]);
+    }
+
+    #[tokio::test]
+    async fn test_anchors_across_sources() {
+        let parser_settings = ParserSettings {
+            dialects: vec![MarkerDialect::MdbookAnchor, MarkerDialect::AsciidocTag],
+            ..Default::default()
+        };
+
+        let file_name_stream = Box::pin(futures::stream::iter(vec![Ok("more_anchors.rs".into()), Ok("anchors.rs".into())].into_iter()));
+        let file_reader_factory = reader_stream(Box::new(StringReaderFactory {}), file_name_stream);
+
+        let examples: Vec<Example> = collect_examples(file_reader_factory, parser_settings).await.unwrap().collect().await;
+        let body = examples.iter().find(|example| example.name == "body").unwrap();
+
+        assert_eq!(body.content, vec!["    run();", "fn run() {}", "fn check() {}"]);
+        assert_eq!(body.sources.iter().map(|source| (source.source_name.as_str(), source.part_number)).collect::<Vec<_>>(),
+                   vec![("anchors.rs", Some(1)), ("anchors.rs", Some(2)), ("more_anchors.rs", Some(3))]);
+    }
+
+    struct GeneratedReaderFactory {}
+
+    impl ReaderFactory<Cursor<String>> for GeneratedReaderFactory {
+        fn make_reader(&self, name: String) -> Result<ReaderContext<Cursor<String>>, ExemplifyError> {
+            let part: u32 = name.trim_start_matches("part-").parse().unwrap();
+            let content = format!("//##exemplify-start##{{name=\"ordered\" part={}}}\nline {}\n//##exemplify-end##\n", part, part);
+
+            Ok(ReaderContext { source_name: name, reader: Cursor::new(content) })
+        }
+    }
+
+    #[test]
+    fn test_order_with_several_sources() {
+        // Several sources, more than there are workers on most machines, given in reverse part order
+        let names: Vec<String> = (1..=40).rev().map(|part| format!("part-{}", part)).collect();
+        let file_name_stream = Box::pin(futures::stream::iter(names.clone().into_iter().map(Ok)));
+        let file_reader_factory = reader_stream(Box::new(GeneratedReaderFactory {}), file_name_stream);
+
+        // Runs without an async runtime
+        let mut result = futures::executor::block_on(collect_examples(file_reader_factory, ParserSettings::default())).unwrap();
+        let example = futures::executor::block_on(result.next()).unwrap();
+
+        assert_eq!(example.content, (1..=40).map(|part| format!("line {}", part)).collect::<Vec<_>>());
+        assert_eq!(example.sources.iter().map(|source| source.source_name.clone()).collect::<Vec<_>>(), names.into_iter().rev().collect::<Vec<_>>());
+    }
+
+    /// Records the threads the sources are read on
+    struct ThreadRecordingReader {
+        content: Cursor<String>,
+        read_on: Arc<Mutex<Vec<std::thread::ThreadId>>>
+    }
+
+    impl Read for ThreadRecordingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.read_on.lock().unwrap().push(std::thread::current().id());
+            self.content.read(buf)
+        }
+    }
+
+    #[test]
+    fn test_sources_read_by_workers() {
+        let read_on = Arc::new(Mutex::new(Vec::new()));
+        let reader_contexts: Vec<_> = (1..=10)
+            .map(|part| Ok(ReaderContext {
+                source_name: format!("part-{}", part),
+                reader: ThreadRecordingReader {
+                    content: Cursor::new(format!("//##exemplify-start##{{name=\"read\" part={}}}\nline\n//##exemplify-end##\n", part)),
+                    read_on: read_on.clone()
+                }
+            }))
+            .collect();
+
+        let mut result = futures::executor::block_on(collect_examples(Box::pin(futures::stream::iter(reader_contexts)), ParserSettings::default())).unwrap();
+
+        assert_eq!(futures::executor::block_on(result.next()).unwrap().content.len(), 10);
+        assert!(!read_on.lock().unwrap().is_empty());
+        assert!(read_on.lock().unwrap().iter().all(|thread| *thread != std::thread::current().id()));
+    }
+
+    #[tokio::test]
+    async fn test_collect_with_cache() {
+        let parser_settings = ParserSettings::default();
+        let mut file_cache = ChunkCache::new(&parser_settings);
+
+        file_cache.insert("a".into(), ChunkCache::content_hash(CONTENT_A.as_bytes()), vec![Chunk { example_name: "cached".into(), ..Default::default() }]);
+        file_cache.insert("b".into(), ChunkCache::content_hash("outdated content".as_bytes()), vec![Chunk { example_name: "outdated".into(), ..Default::default() }]);
+
+        let file_name_stream = Box::pin(futures::stream::iter(
+            vec![
+                Ok("a".into()),
+                Ok("b".into())
+            ].into_iter()));
+
+        let file_reader_factory = reader_stream(Box::new(StringReaderFactory {}), file_name_stream);
+        let result = collect_examples_with_cache(file_reader_factory, parser_settings, &mut file_cache).await.unwrap();
+
+        let mut names: Vec<String> = result.map(|example| example.name).collect().await;
+        names.sort();
+
+        assert_eq!(names, vec!["cached", "example-3"]);
+        assert_eq!(file_cache.get("b", ChunkCache::content_hash(CONTENT_B.as_bytes())).unwrap()[0].example_name, "example-3");
+    }
+
+    #[tokio::test]
+    async fn test_collect_all_errors() {
+        let parser_settings = ParserSettings { collect_all_errors: true, ..Default::default() };
+
+        let file_name_stream = Box::pin(futures::stream::iter(
+            vec![
+                Ok("d".into()),
+                Ok("e".into()),
+                Ok("f".into())
+            ].into_iter()));
+
+        let file_reader_factory = reader_stream(Box::new(StringReaderFactory {}), file_name_stream);
+        let result = collect_examples(file_reader_factory, parser_settings).await;
+
+        let errors = match result {
+            Err(ExemplifyError::Multiple { errors }) => errors,
+            _ => panic!("expected multiple errors")
+        };
+
+        let count = |predicate: fn(&ExemplifyError) -> bool| errors.iter().filter(|e| predicate(e)).count();
+
+        assert_eq!(errors.len(), 8);
+        assert_eq!(count(|e| matches!(e, ExemplifyError::NestedChunk { .. })), 1);
+        assert_eq!(count(|e| matches!(e, ExemplifyError::EndOutsideChunk { .. })), 1);
+        assert_eq!(count(|e| matches!(e, ExemplifyError::MissingName { .. })), 1);
+        assert_eq!(count(|e| matches!(e, ExemplifyError::InvalidAttributes { .. })), 1);
+        assert_eq!(count(|e| matches!(e, ExemplifyError::MissingPartNumber { .. })), 4);
     }
 
     const CONTENT_A: &str = "\
@@ -281,4 +737,99 @@ class ExampleClass {}
 class ExampleClass {}
 //##exemplify-start##{name=\"example-5\"}
         ";
+
+    const CONTENT_HIDE: &str = "\
+//##exemplify-start##{name=\"hidden\"}
+fn main() {
+    // ##exemplify-hide-start##
+    let setup = prepare();
+    // ##exemplify-hide-end##
+    setup.check(); // ##exemplify-hide##
+    run();
+}
+//##exemplify-end##
+        ";
+
+    const CONTENT_ELIDE: &str = "\
+##exemplify-start##{name=\"elided\"}
+def main():
+    # ##exemplify-elide-start##
+    config = load()
+    check(config)
+    # ##exemplify-elide-end##
+    run()
+    report() # ##exemplify-elide##
+##exemplify-end##
+        ";
+
+    const CONTENT_STRINGS: &str = "\
+// ##exemplify-start##{name=\"strings\"}
+const END: &str = \"##exemplify-end##\";
+let end = END;
+// ##exemplify-end##
+        ";
+
+    const CONTENT_HTML: &str = "\
+<!-- ##exemplify-start##{name=\"page\"} -->
+<ul>
+  <!--
+    ##exemplify-elide-start##
+  -->
+  <li>Secret</li>
+  <!-- ##exemplify-elide-end## -->
+</ul>
+<!--
+  ##exemplify-end##
+-->
+        ";
+
+    const CONTENT_ANCHORS: &str = "\
+// ANCHOR: all
+use std::io;
+// ANCHOR: main
+fn main() {
+    // tag::body[]
+    run();
+    // end::body[]
+}
+// ANCHOR_END: main
+// tag::body[]
+fn run() {}
+// ANCHOR_END: all
+// end::body[]
+        ";
+
+    const CONTENT_A_PARTS: &str = "\
+//##exemplify-start##{name=\"parts\" part=4}
+fourth
+//##exemplify-end##
+//##exemplify-start##{name=\"parts\" part=2}
+second
+//##exemplify-end##
+        ";
+
+    const CONTENT_Z_PARTS: &str = "\
+//##exemplify-start##{name=\"parts\" part=1}
+first
+//##exemplify-end##
+//##exemplify-start##{name=\"parts\" part=3}
+third
+//##exemplify-end##
+        ";
+
+    const CONTENT_MORE_ANCHORS: &str = "\
+// tag::body[]
+fn check() {}
+// end::body[]
+        ";
+
+    const CONTENT_FAIL_F: &str = "\
+//##exemplify-end##
+//##exemplify-start##{title=\"Missing name\"}
+class ExampleClass {}
+//##exemplify-end##
+//##exemplify-start##{name=\"example-6\" colour=\"red\"}
+class ExampleClass {}
+//##exemplify-end##
+        ";
 }
@@ -1,5 +1,9 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
 /// This is a raw example chunk - what we extract from the individual source files
-#[derive(Default, Clone)]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct Chunk {
     pub example_name: String,
     pub content: Vec<ChunkLine>,
@@ -9,11 +13,22 @@ pub struct Chunk {
     pub start_line: usize,
     pub title: Option<String>,
     pub language: Option<String>,
-    pub id: Option<String>
+    pub id: Option<String>,
+    /// Byte range of the parameter block on the start marker line
+    pub marker_columns: Option<Range<usize>>,
+    /// Byte range of the part attribute on the start marker line
+    pub part_columns: Option<Range<usize>>,
+    /// The chunk is a region of the mdBook or Asciidoctor dialects. Such regions have no part attribute,
+    /// and are numbered by source name and then line across all sources, once the whole run is read
+    #[serde(default)]
+    pub anchored: bool
 }
 
-#[derive(Default, Clone)]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct ChunkLine {
     pub value: String,
-    pub line_number: usize
+    pub line_number: usize,
+    /// The line stands in for elided code. Its value is the indentation of the elided region,
+    /// as the comment to show depends on the language of the example
+    pub elided: bool
 }
@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::layers::domain::entities::Chunk;
+use crate::layers::domain::error::ExemplifyError;
+use crate::layers::domain::parser_settings::ParserSettings;
+
+/// The chunks extracted from each source file, keyed by the file name and a hash of its content.
+/// Files whose content is unchanged since the cache was made do not have to be parsed again
+#[derive(Serialize, Deserialize, Default)]
+pub struct ChunkCache {
+    /// Identifies the exemplify version and parser settings the chunks were extracted with
+    fingerprint: String,
+    files: BTreeMap<String, CachedFile>
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedFile {
+    hash: u64,
+    chunks: Vec<Chunk>
+}
+
+impl ChunkCache {
+    pub fn new(parser_settings: &ParserSettings) -> ChunkCache {
+        ChunkCache {
+            fingerprint: fingerprint(parser_settings),
+            files: BTreeMap::new()
+        }
+    }
+
+    /// Load a cache written by `to_json`.
+    /// A cache which cannot be read, or was made by another version or with other parser settings, is discarded
+    pub fn from_json(json: &str, parser_settings: &ParserSettings) -> ChunkCache {
+        match serde_json::from_str::<ChunkCache>(json) {
+            Ok(cache) if cache.fingerprint == fingerprint(parser_settings) => cache,
+            _ => ChunkCache::new(parser_settings)
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, ExemplifyError> {
+        serde_json::to_string(self).map_err(|error| ExemplifyError::Serialization { error })
+    }
+
+    /// The hash identifying a version of a source file. It is stable between runs and platforms
+    pub fn content_hash(content: &[u8]) -> u64 {
+        seahash::hash(content)
+    }
+
+    pub fn get(&self, source_name: &str, hash: u64) -> Option<&Vec<Chunk>> {
+        self.files.get(source_name)
+            .filter(|file| file.hash == hash)
+            .map(|file| &file.chunks)
+    }
+
+    pub fn insert(&mut self, source_name: String, hash: u64, chunks: Vec<Chunk>) {
+        self.files.insert(source_name, CachedFile { hash, chunks });
+    }
+}
+
+fn fingerprint(parser_settings: &ParserSettings) -> String {
+    // Only files without errors are cached, so the error reporting mode does not affect the cached chunks
+    let settings = ParserSettings {
+        collect_all_errors: false,
+        ..parser_settings.clone()
+    };
+
+    format!("{} {:?}", env!("CARGO_PKG_VERSION"), settings)
+}
@@ -1,21 +1,53 @@
+use serde::Serialize;
+
 use crate::layers::domain::entities::Printable;
+use crate::layers::domain::error::SourceLocation;
 
+#[derive(Clone)]
 pub struct Example {
     pub name: String,
     pub content: Vec<String>,
     pub title: Option<String>,
     pub language: Option<String>,
-    pub id: Option<String>
+    pub id: Option<String>,
+    pub sources: Vec<ExampleSource>,
+    /// The source line of each content line, when known, with the columns of the line past its indentation
+    pub line_locations: Vec<SourceLocation>
+}
+
+/// The location of a chunk contributing to an example
+#[derive(Clone, Serialize)]
+pub struct ExampleSource {
+    pub source_name: String,
+    pub start_line: usize,
+    pub part_number: Option<u32>
 }
 
 impl Example {
-    pub fn new(name: String, content: Vec<String>, title: Option<String>, language: Option<String>, id: Option<String>) -> Example {
+    pub fn new(name: String, content: Vec<String>, title: Option<String>, language: Option<String>, id: Option<String>, sources: Vec<ExampleSource>) -> Example {
         Example {
             name,
             content,
             title,
             language,
-            id
+            id,
+            sources,
+            line_locations: Vec::new()
+        }
+    }
+
+    pub fn with_line_locations(mut self, line_locations: Vec<SourceLocation>) -> Example {
+        self.line_locations = line_locations;
+        self
+    }
+
+    /// Where the content line with the given index comes from.
+    /// Falls back to the first chunk of the example when the line is not known
+    pub fn line_location(&self, line: usize) -> SourceLocation {
+        match (self.line_locations.get(line), self.sources.first()) {
+            (Some(location), _) => location.clone(),
+            (None, Some(source)) => SourceLocation::new(&source.source_name, source.start_line),
+            _ => SourceLocation::new(&self.name, 0)
         }
     }
 }
@@ -28,4 +60,8 @@ impl Printable for Example {
     fn file_name(&self) -> String {
         self.name.clone()
     }
+
+    fn example_name(&self) -> Option<String> {
+        Some(self.name.clone())
+    }
 }
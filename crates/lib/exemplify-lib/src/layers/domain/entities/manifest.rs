@@ -0,0 +1,62 @@
+use serde::Serialize;
+
+use crate::layers::domain::entities::{Example, ExampleSource};
+use crate::layers::domain::error::ExemplifyError;
+use crate::layers::domain::transforms::callouts::transform_callouts;
+
+/// Machine readable index of every example produced in a run
+#[derive(Serialize, Default)]
+pub struct Manifest {
+    pub examples: Vec<ManifestEntry>
+}
+
+#[derive(Serialize)]
+pub struct ManifestEntry {
+    pub name: String,
+    pub title: Option<String>,
+    pub language: Option<String>,
+    pub id: Option<String>,
+    /// Path of the generated file for the example
+    pub file: String,
+    /// The example content, with the callout markers removed
+    pub content: Vec<String>,
+    pub callouts: Vec<ManifestCallout>,
+    pub chunks: Vec<ExampleSource>
+}
+
+#[derive(Serialize)]
+pub struct ManifestCallout {
+    pub number: usize,
+    pub text: String,
+    /// Index of the content line the callout belongs to
+    pub line: usize
+}
+
+impl ManifestEntry {
+    pub fn new(example: &Example, file: String, callout_token: &str) -> Result<ManifestEntry, ExemplifyError> {
+        let (content, callouts) = transform_callouts(example, callout_token, |_, _| "".into())?;
+
+        Ok(ManifestEntry {
+            name: example.name.clone(),
+            title: example.title.clone(),
+            language: example.language.clone(),
+            id: example.id.clone(),
+            file,
+            content,
+            callouts: callouts.into_iter()
+                .map(|callout| ManifestCallout {
+                    number: callout.number,
+                    text: callout.text,
+                    line: callout.line
+                })
+                .collect(),
+            chunks: example.sources.clone()
+        })
+    }
+}
+
+impl Manifest {
+    pub fn to_json(&self) -> Result<String, ExemplifyError> {
+        serde_json::to_string_pretty(self).map_err(|error| ExemplifyError::Serialization { error })
+    }
+}
@@ -1,7 +1,11 @@
 pub mod printable;
 pub mod example;
 pub mod chunk;
+pub mod chunk_cache;
+pub mod manifest;
 
 pub use printable::*;
 pub use example::*;
 pub use chunk::*;
+pub use chunk_cache::*;
+pub use manifest::*;
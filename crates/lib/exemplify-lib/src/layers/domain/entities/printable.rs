@@ -5,4 +5,23 @@
 pub trait Printable {
     fn print(&self) -> String;
     fn file_name(&self) -> String;
+
+    /// Name of the example printed, or `None` for supporting files such as a stylesheet
+    fn example_name(&self) -> Option<String> {
+        None
+    }
+}
+
+impl<T: Printable + ?Sized> Printable for Box<T> {
+    fn print(&self) -> String {
+        (**self).print()
+    }
+
+    fn file_name(&self) -> String {
+        (**self).file_name()
+    }
+
+    fn example_name(&self) -> Option<String> {
+        (**self).example_name()
+    }
 }
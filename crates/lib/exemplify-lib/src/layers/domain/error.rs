@@ -0,0 +1,157 @@
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
+/// A position in one of the scanned source files
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLocation {
+    pub source_name: String,
+    pub line: usize,
+    /// Byte range within the line the location points at, if known
+    pub columns: Option<Range<usize>>
+}
+
+impl SourceLocation {
+    pub fn new(source_name: &str, line: usize) -> SourceLocation {
+        SourceLocation {
+            source_name: source_name.to_string(),
+            line,
+            columns: None
+        }
+    }
+
+    pub fn with_columns(mut self, columns: Option<Range<usize>>) -> SourceLocation {
+        self.columns = columns;
+        self
+    }
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}[{}]", self.source_name, self.line)
+    }
+}
+
+#[derive(Debug)]
+pub enum ExemplifyError {
+    /// A chunk was started while another chunk was still open
+    NestedChunk { location: SourceLocation, open_chunk: SourceLocation },
+    /// An end token was found outside of any chunk
+    EndOutsideChunk { location: SourceLocation },
+    /// Two chunks of the same example have the same part number
+    DuplicatePart { location: SourceLocation, part: u32, previous: SourceLocation },
+    /// A chunk of an example with several chunks has no part number
+    MissingPartNumber { location: SourceLocation },
+    /// A chunk start marker has no name attribute
+    MissingName { location: SourceLocation },
+    /// An integer attribute could not be parsed
+    InvalidIntegerAttribute { location: SourceLocation, attribute: String, value: String },
+    /// The attribute block of a marker is malformed, or has unknown or repeated attributes
+    InvalidAttributes { location: SourceLocation, message: String },
+    /// A callout marker without a value, or with a malformed attribute block
+    InvalidCallout { location: SourceLocation, message: String },
+    NotAFile { path: String },
+    InvalidFileName { path: String },
+    /// An include or exclude glob could not be parsed
+    InvalidGlob { glob: String, message: String },
+    Io { path: String, error: std::io::Error },
+    Highlighting { message: String },
+    /// A thread chunking source files panicked
+    Worker { message: String },
+    Serialization { error: serde_json::Error },
+    /// The source folder could not be watched for changes
+    Watch { message: String },
+    /// The configuration file could not be parsed
+    InvalidConfig { path: String, message: String },
+    /// A required setting was given neither on the command line nor in the configuration file
+    MissingSetting { setting: String, required_by: Option<String> },
+    /// Some generated files differ from the files in the output folder
+    OutOfDate { files: usize },
+    /// Every error found in a run, when not failing on the first one
+    Multiple { errors: Vec<ExemplifyError> }
+}
+
+impl Display for ExemplifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExemplifyError::NestedChunk { location, open_chunk } =>
+                write!(f, "{}: attempting to start chunk-in-chunk, the chunk started at {} is still open", location, open_chunk),
+            ExemplifyError::EndOutsideChunk { location } =>
+                write!(f, "{}: attempting to end chunk outside of chunk", location),
+            ExemplifyError::DuplicatePart { location, part, previous } =>
+                write!(f, "{}: Duplicate part {}, previously defined at {}", location, part, previous),
+            ExemplifyError::MissingPartNumber { location } =>
+                write!(f, "{}: You must provide a part number for chunks in examples with more than one chunk", location),
+            ExemplifyError::MissingName { location } =>
+                write!(f, "{}: Missing name", location),
+            ExemplifyError::InvalidIntegerAttribute { location, attribute, value } =>
+                write!(f, "{}: Failed to parse {} number {}", location, attribute, value),
+            ExemplifyError::InvalidAttributes { location, message } =>
+                write!(f, "{}: Invalid attributes: {}", location, message),
+            ExemplifyError::InvalidCallout { location, message } =>
+                write!(f, "{}: Invalid callout: {}", location, message),
+            ExemplifyError::NotAFile { path } =>
+                write!(f, "{} is not a file", path),
+            ExemplifyError::InvalidFileName { path } =>
+                write!(f, "Invalid filename {}", path),
+            ExemplifyError::InvalidGlob { glob, message } =>
+                write!(f, "Invalid glob {}: {}", glob, message),
+            ExemplifyError::Io { path, error } =>
+                write!(f, "{}: {}", path, error),
+            ExemplifyError::Highlighting { message } =>
+                write!(f, "Syntax highlighting failed: {}", message),
+            ExemplifyError::Serialization { error } =>
+                write!(f, "Serialization failed: {}", error),
+            ExemplifyError::Worker { message } =>
+                write!(f, "Chunking the source files failed unexpectedly: {}", message),
+            ExemplifyError::Watch { message } =>
+                write!(f, "Watching for changes failed: {}", message),
+            ExemplifyError::InvalidConfig { path, message } =>
+                write!(f, "Invalid configuration file {}: {}", path, message),
+            ExemplifyError::MissingSetting { setting, required_by: None } =>
+                write!(f, "No {} given", setting),
+            ExemplifyError::MissingSetting { setting, required_by: Some(option) } =>
+                write!(f, "No {} given, but {} requires one", setting, option),
+            ExemplifyError::OutOfDate { files } =>
+                write!(f, "{} generated files are not up to date", files),
+            ExemplifyError::Multiple { errors } => {
+                let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+
+                write!(f, "{}", messages.join("\n"))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExemplifyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExemplifyError::Io { error, .. } => Some(error),
+            ExemplifyError::Serialization { error } => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl ExemplifyError {
+    /// The source location the error refers to, if any
+    pub fn location(&self) -> Option<&SourceLocation> {
+        match self {
+            ExemplifyError::NestedChunk { location, .. } |
+            ExemplifyError::EndOutsideChunk { location } |
+            ExemplifyError::DuplicatePart { location, .. } |
+            ExemplifyError::MissingPartNumber { location } |
+            ExemplifyError::MissingName { location } |
+            ExemplifyError::InvalidIntegerAttribute { location, .. } |
+            ExemplifyError::InvalidAttributes { location, .. } |
+            ExemplifyError::InvalidCallout { location, .. } => Some(location),
+            _ => None
+        }
+    }
+
+    pub fn io(path: &str, error: std::io::Error) -> ExemplifyError {
+        ExemplifyError::Io {
+            path: path.to_string(),
+            error
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::Path;
+
+/// How comments are written in a language
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CommentSyntax {
+    pub line: Option<&'static str>,
+    pub block: Option<(&'static str, &'static str)>,
+    /// The literals of the language, in which comment delimiters have no meaning
+    pub quotes: &'static [Quote]
+}
+
+/// A kind of literal a language quotes text with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Quote {
+    /// A string between two of the character, in which a backslash escapes the next character
+    String(char),
+    /// A single character between `'`, such as `'"'` or `'\n'`. Any other `'`, such as that of a Rust lifetime, quotes nothing
+    Char
+}
+
+const C_LIKE: CommentSyntax = CommentSyntax { line: Some("//"), block: Some(("/*", "*/")), quotes: &[Quote::String('"'), Quote::Char] };
+const C_LIKE_QUOTED: CommentSyntax = CommentSyntax { line: Some("//"), block: Some(("/*", "*/")), quotes: &[Quote::String('"'), Quote::String('\'')] };
+const JAVASCRIPT: CommentSyntax = CommentSyntax { line: Some("//"), block: Some(("/*", "*/")), quotes: &[Quote::String('"'), Quote::String('\''), Quote::String('`')] };
+const GO: CommentSyntax = CommentSyntax { line: Some("//"), block: Some(("/*", "*/")), quotes: &[Quote::String('"'), Quote::Char, Quote::String('`')] };
+const HASH: CommentSyntax = CommentSyntax { line: Some("#"), block: None, quotes: &[Quote::String('"'), Quote::String('\'')] };
+const DOUBLE_DASH: CommentSyntax = CommentSyntax { line: Some("--"), block: None, quotes: &[Quote::String('"'), Quote::String('\'')] };
+const SQL: CommentSyntax = CommentSyntax { line: Some("--"), block: Some(("/*", "*/")), quotes: &[Quote::String('"'), Quote::String('\'')] };
+const MARKUP: CommentSyntax = CommentSyntax { line: None, block: Some(("<!--", "-->")), quotes: &[] };
+const STYLESHEET: CommentSyntax = CommentSyntax { line: None, block: Some(("/*", "*/")), quotes: &[Quote::String('"'), Quote::String('\'')] };
+
+/// The comment syntax of a language, given either by name or by file extension
+pub fn comment_syntax(language: &str) -> Option<CommentSyntax> {
+    match language.to_lowercase().as_str() {
+        "rust" | "rs" | "c" | "h" | "cpp" | "c++" | "cc" | "hpp" | "csharp" | "c#" | "cs" | "java" | "kotlin" | "kt" | "scala"
+        | "swift" => Some(C_LIKE),
+        "groovy" | "dart" | "php" | "protobuf" | "proto" | "scss" | "less" => Some(C_LIKE_QUOTED),
+        "javascript" | "js" | "jsx" | "typescript" | "ts" | "tsx" => Some(JAVASCRIPT),
+        "go" => Some(GO),
+        "python" | "py" | "ruby" | "rb" | "perl" | "pl" | "r" | "shell" | "sh" | "bash" | "zsh" | "powershell" | "ps1"
+        | "yaml" | "yml" | "toml" | "dockerfile" | "makefile" | "make" | "cmake" | "elixir" | "ex" | "exs" | "nix" => Some(HASH),
+        "sql" => Some(SQL),
+        "lua" | "haskell" | "hs" | "elm" | "ada" => Some(DOUBLE_DASH),
+        "html" | "htm" | "xml" | "svg" | "xhtml" | "markdown" | "md" => Some(MARKUP),
+        "css" => Some(STYLESHEET),
+        _ => None
+    }
+}
+
+impl CommentSyntax {
+    /// A comment containing the given text, preferring a line comment if the language has them
+    pub fn comment(&self, text: &str) -> String {
+        match (self.line, self.block) {
+            (Some(line), _) => format!("{} {}", line, text),
+            (None, Some((start, end))) => format!("{} {} {}", start, text, end),
+            (None, None) => text.to_string()
+        }
+    }
+}
+
+/// Finds the comments of a source file line by line, following block comments across lines.
+/// Comment delimiters in string literals are skipped, strings are assumed to end on the line they start
+pub struct CommentScanner {
+    syntax: CommentSyntax,
+    /// End delimiter of a block comment left open on an earlier line
+    open_block: Option<&'static str>
+}
+
+impl CommentScanner {
+    pub fn new(syntax: CommentSyntax) -> CommentScanner {
+        CommentScanner { syntax, open_block: None }
+    }
+
+    /// Whether a block comment is left open by the lines scanned so far
+    pub fn in_block_comment(&self) -> bool {
+        self.open_block.is_some()
+    }
+
+    /// Byte ranges of the comments on the next line of the file, delimiters included
+    pub fn comments(&mut self, line: &str) -> Vec<Range<usize>> {
+        let mut comments = Vec::new();
+        let mut position = 0;
+        let mut quote = None;
+
+        if let Some(end) = self.open_block {
+            match line.find(end) {
+                Some(found) => {
+                    position = found + end.len();
+                    comments.push(0..position);
+                    self.open_block = None;
+                }
+                _ => {
+                    comments.push(0..line.len());
+                    return comments;
+                }
+            }
+        }
+
+        while let Some(character) = line[position..].chars().next() {
+            let rest = &line[position..];
+
+            match quote {
+                Some(_) if character == '\\' => {
+                    position += character.len_utf8();
+                    position += line[position..].chars().next().map(char::len_utf8).unwrap_or(0);
+                    continue;
+                }
+                Some(open_quote) if character == open_quote => quote = None,
+                Some(_) => {}
+                None => {
+                    if self.syntax.line.map(|start| rest.starts_with(start)).unwrap_or(false) {
+                        comments.push(position..line.len());
+                        return comments;
+                    }
+
+                    if let Some((start, end)) = self.syntax.block.filter(|(start, _)| rest.starts_with(start)) {
+                        match rest[start.len()..].find(end) {
+                            Some(found) => {
+                                let comment_end = position + start.len() + found + end.len();
+
+                                comments.push(position..comment_end);
+                                position = comment_end;
+                                continue;
+                            }
+                            _ => {
+                                self.open_block = Some(end);
+                                comments.push(position..line.len());
+                                return comments;
+                            }
+                        }
+                    }
+
+                    if self.syntax.quotes.contains(&Quote::String(character)) {
+                        quote = Some(character);
+                    } else if character == '\'' && self.syntax.quotes.contains(&Quote::Char) {
+                        if let Some(length) = char_literal_length(rest) {
+                            position += length;
+                            continue;
+                        }
+                    }
+                }
+            }
+
+            position += character.len_utf8();
+        }
+
+        comments
+    }
+}
+
+/// Byte length of the character literal at the start of the text, if it starts with one.
+/// Escapes may be longer than a character, as in `'\u{1F600}'`, but are still short
+fn char_literal_length(text: &str) -> Option<usize> {
+    let mut characters = text.char_indices().skip(1);
+
+    match characters.next()? {
+        (_, '\\') => {
+            characters.next();
+            characters.take(10).find(|(_, character)| *character == '\'').map(|(position, _)| position + 1)
+        }
+        _ => match characters.next()? {
+            (position, '\'') => Some(position + 1),
+            _ => None
+        }
+    }
+}
+
+/// Languages of files recognised by their name rather than an extension, matched case-insensitively
+const FILE_NAME_LANGUAGES: &[(&str, &str)] = &[
+    ("makefile", "makefile"), ("gnumakefile", "makefile"), ("dockerfile", "dockerfile"), ("containerfile", "dockerfile")
+];
+
+/// The comment syntax of a source file, from the language its extension maps to or else the extension itself.
+/// Files such as `Makefile` and `Dockerfile` are recognised by their name
+pub fn comment_syntax_for_source(source_name: &str, overrides: &BTreeMap<String, String>) -> Option<CommentSyntax> {
+    let path = Path::new(source_name);
+
+    language_for_source(source_name, overrides)
+        .and_then(|language| comment_syntax(&language))
+        .or_else(|| comment_syntax(&path.extension()?.to_string_lossy()))
+        .or_else(|| {
+            let file_name = path.file_name()?.to_string_lossy().to_lowercase();
+
+            FILE_NAME_LANGUAGES.iter()
+                .find(|(name, _)| *name == file_name)
+                .and_then(|(_, language)| comment_syntax(language))
+        })
+}
+
+/// The line standing in for elided code, for a language given by name or file extension
+pub fn elision_comment(language: Option<&str>) -> String {
+    language.and_then(comment_syntax)
+        .unwrap_or(C_LIKE)
+        .comment("...")
+}
+
+/// The language of source files by extension, for chunks without a `language` attribute
+const EXTENSION_LANGUAGES: &[(&str, &str)] = &[
+    ("rs", "rust"), ("c", "c"), ("h", "c"), ("cpp", "cpp"), ("cc", "cpp"), ("hpp", "cpp"), ("cs", "csharp"),
+    ("java", "java"), ("kt", "kotlin"), ("scala", "scala"), ("groovy", "groovy"), ("go", "go"), ("swift", "swift"),
+    ("dart", "dart"), ("php", "php"), ("js", "javascript"), ("jsx", "jsx"), ("ts", "typescript"), ("tsx", "tsx"),
+    ("py", "python"), ("rb", "ruby"), ("pl", "perl"), ("r", "r"), ("sh", "bash"), ("bash", "bash"), ("zsh", "zsh"),
+    ("ps1", "powershell"), ("ex", "elixir"), ("exs", "elixir"), ("nix", "nix"), ("sql", "sql"), ("lua", "lua"),
+    ("hs", "haskell"), ("elm", "elm"), ("html", "html"), ("htm", "html"), ("xml", "xml"), ("svg", "xml"),
+    ("css", "css"), ("scss", "scss"), ("less", "less"), ("json", "json"), ("yaml", "yaml"), ("yml", "yaml"),
+    ("toml", "toml"), ("md", "markdown"), ("proto", "protobuf")
+];
+
+/// The language of a source file, found from its extension.
+/// `overrides` maps extensions, with or without the leading dot, to languages, taking precedence over the built-in table.
+/// An empty language in the overrides turns detection off for the extension
+pub fn language_for_source(source_name: &str, overrides: &BTreeMap<String, String>) -> Option<String> {
+    let extension = Path::new(source_name).extension()?.to_string_lossy().to_lowercase();
+
+    let overridden = overrides.iter()
+        .find(|(overridden, _)| overridden.trim_start_matches('.').to_lowercase() == extension);
+
+    let language = match overridden {
+        Some((_, language)) => language.as_str(),
+        _ => EXTENSION_LANGUAGES.iter().find(|(known, _)| *known == extension)?.1
+    };
+
+    match language.is_empty() {
+        true => None,
+        false => Some(language.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_language_for_source() {
+        let overrides = vec![(".h".to_string(), "cpp".to_string()), ("txt".to_string(), "".to_string()), ("tpl".to_string(), "handlebars".to_string())]
+            .into_iter().collect();
+
+        assert_eq!(language_for_source("src/main.rs", &BTreeMap::new()).as_deref(), Some("rust"));
+        assert_eq!(language_for_source("src/App.TS", &BTreeMap::new()).as_deref(), Some("typescript"));
+        assert_eq!(language_for_source("Makefile", &BTreeMap::new()), None);
+        assert_eq!(language_for_source("include/api.h", &overrides).as_deref(), Some("cpp"));
+        assert_eq!(language_for_source("views/page.tpl", &overrides).as_deref(), Some("handlebars"));
+        assert_eq!(language_for_source("notes.txt", &overrides), None);
+    }
+
+    #[test]
+    fn test_comment_syntax_for_source() {
+        let line_comment = |source_name: &str| comment_syntax_for_source(source_name, &BTreeMap::new()).and_then(|syntax| syntax.line);
+
+        assert_eq!(line_comment("src/main.rs"), Some("//"));
+        assert_eq!(line_comment("build/Makefile"), Some("#"));
+        assert_eq!(line_comment("makefile"), Some("#"));
+        assert_eq!(line_comment("docker/Dockerfile"), Some("#"));
+        assert_eq!(line_comment("LICENSE"), None);
+    }
+
+    #[test]
+    fn test_comment_scanner() {
+        let comments = |scanner: &mut CommentScanner, line: &str| -> Vec<String> {
+            scanner.comments(line).into_iter().map(|range| line[range].to_string()).collect()
+        };
+
+        let mut rust = CommentScanner::new(comment_syntax("rust").unwrap());
+
+        assert_eq!(comments(&mut rust, r#"let s = "// \" /*"; /* a */ f(); // b"#), vec!["/* a */", "// b"]);
+        assert_eq!(comments(&mut rust, "x /* open"), vec!["/* open"]);
+        assert_eq!(comments(&mut rust, "still open"), vec!["still open"]);
+        assert_eq!(comments(&mut rust, "closed */ y"), vec!["closed */"]);
+        assert!(comments(&mut rust, "y").is_empty());
+
+        assert_eq!(comments(&mut rust, "let q = '\"'; // ##exemplify-hide##"), vec!["// ##exemplify-hide##"]);
+        assert_eq!(comments(&mut rust, r#"let q = '\''; let s = "a"; // c"#), vec!["// c"]);
+        assert_eq!(comments(&mut rust, r#"fn f<'a>(s: &'a str) -> &'static str { "/*" } // c"#), vec!["// c"]);
+
+        let mut java = CommentScanner::new(comment_syntax("java").unwrap());
+
+        assert_eq!(comments(&mut java, r#"char c = '\u0022'; /* a */ char d = '"'; // b"#), vec!["/* a */", "// b"]);
+
+        let mut typescript = CommentScanner::new(comment_syntax("ts").unwrap());
+
+        assert_eq!(comments(&mut typescript, "const marker = '// ##exemplify-end##';"), Vec::<String>::new());
+        assert_eq!(comments(&mut typescript, "const s = `/* ${'\"'} */`; // c"), vec!["// c"]);
+
+        let mut html = CommentScanner::new(comment_syntax("html").unwrap());
+
+        assert_eq!(comments(&mut html, "<p>\"<!-- a -->\"</p>"), vec!["<!-- a -->"]);
+    }
+}
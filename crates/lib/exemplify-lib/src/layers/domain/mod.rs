@@ -1,7 +1,10 @@
+pub mod attributes;
 pub mod chunk_reader;
 pub mod collect_examples;
+pub mod languages;
 pub mod reader_factory;
 pub mod reader_stream;
 pub mod parser_settings;
 pub mod transforms;
 pub mod entities;
+pub mod error;
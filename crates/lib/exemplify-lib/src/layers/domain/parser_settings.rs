@@ -1,5 +1,73 @@
-#[derive(Clone)]
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// A syntax for marking the regions of source files which make up the examples
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MarkerDialect {
+    /// The start and end tokens of the parser settings, with an attribute block
+    Exemplify,
+    /// mdBook anchors, `ANCHOR: name` and `ANCHOR_END: name`
+    MdbookAnchor,
+    /// Asciidoctor include tags, `tag::name[]` and `end::name[]`
+    AsciidocTag
+}
+
+impl FromStr for MarkerDialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exemplify" => Ok(MarkerDialect::Exemplify),
+            "mdbook-anchor" => Ok(MarkerDialect::MdbookAnchor),
+            "asciidoc-tag" => Ok(MarkerDialect::AsciidocTag),
+            _ => Err("invalid marker dialect".into())
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct ParserSettings {
     pub start_token: String,
     pub end_token: String,
+    /// Keep going after an error, and report every error found once all sources are processed
+    pub collect_all_errors: bool,
+    /// Lines of a chunk between the hide start and end tokens are left out of the example
+    pub hide_start_token: String,
+    pub hide_end_token: String,
+    /// A line of a chunk containing this token is left out of the example
+    pub hide_token: String,
+    /// Lines of a chunk between the elide start and end tokens are replaced by a single `...` comment
+    pub elide_start_token: String,
+    pub elide_end_token: String,
+    /// A line of a chunk containing this token is replaced by a `...` comment
+    pub elide_token: String,
+    /// Languages of file extensions, without the leading dot, adding to or overriding the built-in table.
+    /// Examples without a `language` attribute get the language of their first chunk's source file
+    pub extension_languages: BTreeMap<String, String>,
+    /// Recognise markers anywhere on a line, rather than only in the comments of the source language.
+    /// Markers in files of unknown languages are always recognised anywhere
+    pub markers_anywhere: bool,
+    /// The marker syntaxes to recognise. The regions of the mdBook and Asciidoctor dialects may overlap,
+    /// and each becomes a chunk of the example it names. The regions of an example are numbered across all sources,
+    /// ordered by source name and then line
+    pub dialects: Vec<MarkerDialect>,
+}
+
+impl Default for ParserSettings {
+    fn default() -> Self {
+        ParserSettings {
+            start_token: "##exemplify-start##".into(),
+            end_token: "##exemplify-end##".into(),
+            collect_all_errors: false,
+            hide_start_token: "##exemplify-hide-start##".into(),
+            hide_end_token: "##exemplify-hide-end##".into(),
+            hide_token: "##exemplify-hide##".into(),
+            elide_start_token: "##exemplify-elide-start##".into(),
+            elide_end_token: "##exemplify-elide-end##".into(),
+            elide_token: "##exemplify-elide##".into(),
+            extension_languages: BTreeMap::new(),
+            markers_anywhere: false,
+            dialects: vec![MarkerDialect::Exemplify],
+        }
+    }
 }
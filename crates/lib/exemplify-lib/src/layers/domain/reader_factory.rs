@@ -1,7 +1,9 @@
 use std::io::Read;
 
+use crate::layers::domain::error::ExemplifyError;
+
 pub trait ReaderFactory<Reader: Read> {
-    fn make_reader(&self, name: String) -> Result<ReaderContext<Reader>, String>;
+    fn make_reader(&self, name: String) -> Result<ReaderContext<Reader>, ExemplifyError>;
 }
 
 pub struct ReaderContext<Reader: Read> {
@@ -7,11 +7,15 @@ use std::pin::Pin;
 use futures::{Stream, StreamExt};
 
 
+use crate::layers::domain::error::ExemplifyError;
 use crate::layers::domain::reader_factory::{ReaderFactory, ReaderContext};
 
+/// The names of the sources to read, such as the paths found by file discovery
+pub type SourceNameStream = Pin<Box<dyn Stream<Item=Result<String, ExemplifyError>>>>;
+
 pub fn reader_stream<Reader: Read + 'static>(
     reader_factory: Box<dyn ReaderFactory<Reader>>,
-    file_path_stream: Pin<Box<dyn Stream<Item=Result<String, String>>>>) -> Pin<Box<dyn Stream<Item=Result<ReaderContext<Reader>, String>>>> {
+    file_path_stream: SourceNameStream) -> Pin<Box<dyn Stream<Item=Result<ReaderContext<Reader>, ExemplifyError>>>> {
     Box::pin(file_path_stream.map(move |path| {
         match path {
             Err(e) => Err(e),
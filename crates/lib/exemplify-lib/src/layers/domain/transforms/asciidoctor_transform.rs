@@ -2,6 +2,9 @@ use std::pin::Pin;
 
 use futures::{Stream, StreamExt};
 use crate::layers::domain::entities::{Example, Printable};
+use crate::layers::domain::error::ExemplifyError;
+use crate::layers::domain::transforms::callouts::transform_callouts;
+use crate::layers::domain::transforms::markers::EnclosingComment;
 
 pub struct AsciidoctorSettings {
     pub callout_token: String
@@ -19,14 +22,22 @@ impl Printable for AsciidoctorExample {
     fn file_name(&self) -> String {
         format!("{}.adoc", self.inner.name)
     }
+
+    fn example_name(&self) -> Option<String> {
+        self.inner.example_name()
+    }
 }
 
-pub fn map_to_asciidoctor(input: Pin<Box<dyn Stream<Item=Example>>>, settings: AsciidoctorSettings) -> Pin<Box<dyn Stream<Item=Result<AsciidoctorExample, String>>>> {
+pub fn map_to_asciidoctor(input: Pin<Box<dyn Stream<Item=Example>>>, settings: AsciidoctorSettings) -> Pin<Box<dyn Stream<Item=Result<AsciidoctorExample, ExemplifyError>>>> {
     Box::pin(input.map(move |example| {
         let header = create_asciidoc_source_header(&settings, &example);
         let footer = create_asciidoc_source_footer(&settings);
 
-        let content = transform_callouts(example.content, &settings.callout_token)?;
+        let content = transform_callouts(&example, &settings.callout_token, |number, comment| match comment {
+            // Asciidoctor has a callout syntax of its own for markup
+            Some(EnclosingComment { start: "<!--", .. }) => format!("<!--{}-->", number),
+            _ => format!("<{}>", number)
+        })?;
         let callouts = content.1
             .into_iter()
             .map(|callout| format!("<{}> {}", callout.number, callout.text))
@@ -43,7 +54,9 @@ pub fn map_to_asciidoctor(input: Pin<Box<dyn Stream<Item=Example>>>, settings: A
                 ].into_iter().flatten().collect(),
                 title: example.title,
                 language: example.language,
-                id: example.id
+                id: example.id,
+                sources: example.sources,
+                line_locations: Vec::new()
             }
         })
     }))
@@ -76,56 +89,3 @@ fn create_asciidoc_source_footer(_settings: &AsciidoctorSettings) -> Vec<String>
         "----".into()
     ]
 }
-
-struct Callout {
-    text: String,
-    number: usize
-}
-
-fn transform_callouts(input: Vec<String>, callout_token: &String) -> Result<(Vec<String>, Vec<Callout>), String> {
-    let mut callout_number = 1;
-    let mut output = Vec::new();
-    let mut callouts= Vec::new();
-
-    for mut line in input {
-        loop {
-            if line.contains(callout_token) {
-                line = line.replacen(callout_token, format!("<{}>", callout_number).as_str(), 1);
-                let extract = extract_first_callout(line, callout_number)
-                    .map_err(|e| format!("Failed extracting callout from {}", e))?;
-
-                line = extract.0;
-                callouts.push(extract.1);
-
-                callout_number += 1;
-            } else {
-                break;
-            }
-        }
-
-        output.push(line);
-    }
-
-    Ok((output, callouts))
-}
-
-fn extract_first_callout(mut input: String, idx: usize) -> Result<(String, Callout), String> {
-    lazy_static::lazy_static! {
-        static ref CALLOUT_RE: regex::Regex = regex::Regex::new("\\{value=\"(.+)\"\\}").unwrap();
-    }
-
-    for val in CALLOUT_RE.captures_iter(input.clone().as_str()) {
-        let value = val.get(1);
-
-        if let Some(value) = value {
-            input = CALLOUT_RE.replace(input.as_str(), "").to_string();
-
-            return Ok((input, Callout {
-                text: value.as_str().to_string().clone(),
-                number: idx
-            }))
-        }
-    }
-
-    Err(input)
-}
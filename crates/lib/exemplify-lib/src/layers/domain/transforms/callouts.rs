@@ -0,0 +1,143 @@
+use std::ops::Range;
+
+use crate::layers::domain::attributes::{AttributeError, parse_attribute_block};
+use crate::layers::domain::entities::Example;
+use crate::layers::domain::error::{ExemplifyError, SourceLocation};
+use crate::layers::domain::transforms::markers::{EnclosingComment, enclosing_comment, strip_marker};
+
+pub struct Callout {
+    pub text: String,
+    pub number: usize,
+    /// Index of the content line containing the callout
+    pub line: usize
+}
+
+/// Replace every callout token in the input with the marker produced by `format_marker`,
+/// and collect the callout texts in order of appearance.
+/// For a callout alone in a block comment, `format_marker` is given the comment and its marker replaces the whole comment.
+/// An empty marker removes the callout, along with the comment it leaves empty
+pub fn transform_callouts<F: Fn(usize, Option<EnclosingComment>) -> String>(example: &Example, callout_token: &str, format_marker: F) -> Result<(Vec<String>, Vec<Callout>), ExemplifyError> {
+    let mut callout_number = 1;
+    let mut output = Vec::new();
+    let mut callouts= Vec::new();
+
+    for (line_index, mut line) in example.content.iter().cloned().enumerate() {
+        while let Some(start) = line.find(callout_token) {
+            let attributes_start = start + callout_token.len();
+            let (text, end) = extract_callout_text(&line, start, attributes_start)
+                .map_err(|error| ExemplifyError::InvalidCallout {
+                    location: callout_location(example, line_index, &line, error.columns),
+                    message: error.message
+                })?;
+
+            let comment = enclosing_comment(&line, start, end);
+
+            line = match (format_marker(callout_number, comment.as_ref().map(|(comment, _)| *comment)), comment) {
+                (marker, _) if marker.is_empty() => strip_marker(&line, start, end),
+                (marker, Some((_, columns))) => format!("{}{}{}", &line[..columns.start], marker, &line[columns.end..]),
+                (marker, _) => format!("{}{}{}", &line[..start], marker, &line[end..])
+            };
+
+            callouts.push(Callout {
+                text,
+                number: callout_number,
+                line: line_index
+            });
+
+            callout_number += 1;
+        }
+
+        output.push(line);
+    }
+
+    Ok((output, callouts))
+}
+
+/// The callout text, and the end of the attribute block which must directly follow the callout token
+fn extract_callout_text(line: &str, token_start: usize, attributes_start: usize) -> Result<(String, usize), AttributeError> {
+    let missing_value = |end: usize| AttributeError {
+        message: "the callout has no value attribute".into(),
+        columns: token_start..end
+    };
+
+    let block = match parse_attribute_block(line, attributes_start) {
+        Some(block) if line[attributes_start..].starts_with('{') => block?,
+        _ => return Err(missing_value(attributes_start))
+    };
+
+    block.check_keys(&["value"])?;
+
+    match block.text("value")? {
+        Some(text) => Ok((text, block.columns.end)),
+        _ => Err(missing_value(block.columns.end))
+    }
+}
+
+/// The source location of the given columns of a content line, whose earlier callouts may already be replaced.
+/// The content line and its source line only differ in indentation, which the location columns leave out
+fn callout_location(example: &Example, line_index: usize, line: &str, columns: Range<usize>) -> SourceLocation {
+    let location = example.line_location(line_index);
+    let content_line = &example.content[line_index];
+
+    // Every replacement so far is before the columns, so the rest of the line is unchanged
+    let replaced_length = content_line.len() as isize - line.len() as isize;
+    let content_indentation = (content_line.len() - content_line.trim_start().len()) as isize;
+
+    let source_columns = location.columns.clone().map(|source_columns| {
+        let to_source = |column: usize| (source_columns.start as isize + column as isize + replaced_length - content_indentation).max(0) as usize;
+
+        to_source(columns.start)..to_source(columns.end)
+    });
+
+    location.with_columns(source_columns)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn example(line: &str, source_line: &str) -> Example {
+        let indentation = source_line.len() - source_line.trim_start().len();
+
+        Example::new("example".into(), vec![line.into()], None, None, None, vec![])
+            .with_line_locations(vec![SourceLocation::new("a.rs", 3).with_columns(Some(indentation..source_line.len()))])
+    }
+
+    fn callout_error(line: &str, source_line: &str) -> (String, Option<Range<usize>>) {
+        match transform_callouts(&example(line, source_line), "##callout##", |number, _| format!("({})", number)) {
+            Err(ExemplifyError::InvalidCallout { location, message }) => (message, location.columns),
+            _ => panic!("expected an invalid callout")
+        }
+    }
+
+    #[test]
+    fn test_callouts() {
+        let (content, callouts) = transform_callouts(&example("a(); // ##callout##{value=\"A\"}", ""), "##callout##", |number, _| format!("({})", number)).unwrap();
+
+        assert_eq!(content, vec!["a(); // (1)"]);
+        assert_eq!(callouts[0].text, "A");
+    }
+
+    #[test]
+    fn test_invalid_callouts() {
+        // The content line is less indented than its source line
+        let source_line = "        a(); // ##callout##{value=\"A\"} ##callout##{value=\"B\" colour=\"red\"}";
+        let line = source_line.trim_start();
+        let (message, columns) = callout_error(line, source_line);
+
+        assert!(message.starts_with("unknown attribute colour"));
+        assert_eq!(&source_line[columns.unwrap()], "colour");
+
+        let source_line = "a(); // ##callout##{value=\"A\" value=\"B\"}";
+        let (message, columns) = callout_error(source_line, source_line);
+
+        assert!(message.contains("value"));
+        assert_eq!(&source_line[columns.unwrap()], "value");
+
+        let source_line = "a(); // ##callout## no block";
+        let (message, columns) = callout_error(source_line, source_line);
+
+        assert_eq!(message, "the callout has no value attribute");
+        assert_eq!(&source_line[columns.unwrap()], "##callout##");
+    }
+}
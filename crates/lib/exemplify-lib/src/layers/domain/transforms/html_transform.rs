@@ -0,0 +1,257 @@
+use std::pin::Pin;
+
+use futures::{Stream, StreamExt};
+use syntect::highlighting::ThemeSet;
+use syntect::html::{ClassStyle, ClassedHTMLGenerator, css_for_theme_with_class_style};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+use crate::layers::domain::entities::{Example, Printable};
+use crate::layers::domain::error::ExemplifyError;
+use crate::layers::domain::transforms::callouts::transform_callouts;
+use crate::layers::domain::transforms::markers::EnclosingComment;
+
+/// Name of the stylesheet shared by all html examples in an output folder
+pub const HTML_STYLESHEET_NAME: &str = "exemplify.css";
+
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "exemplify-" };
+const HIGHLIGHT_THEME: &str = "InspiredGitHub";
+
+// Private use code points survive both the highlighter and html escaping,
+// so they are used to mark callout positions until the highlighted output is ready
+const CALLOUT_PLACEHOLDER_START: char = '\u{E000}';
+const CALLOUT_PLACEHOLDER_END: char = '\u{E001}';
+
+lazy_static::lazy_static! {
+    static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
+}
+
+pub struct HtmlSettings {
+    pub callout_token: String
+}
+
+pub struct HtmlExample {
+    inner: Example
+}
+
+impl Printable for HtmlExample {
+    fn print(&self) -> String {
+        self.inner.print()
+    }
+
+    fn file_name(&self) -> String {
+        format!("{}.html", self.inner.name)
+    }
+
+    fn example_name(&self) -> Option<String> {
+        self.inner.example_name()
+    }
+}
+
+/// The companion stylesheet for the html examples, containing both the syntax highlighting and callout styles
+pub struct HtmlStylesheet {
+    content: String
+}
+
+impl HtmlStylesheet {
+    pub fn new() -> Result<HtmlStylesheet, ExemplifyError> {
+        let themes = ThemeSet::load_defaults();
+        let theme = themes.themes.get(HIGHLIGHT_THEME).ok_or(ExemplifyError::Highlighting { message: format!("Missing highlighting theme {}", HIGHLIGHT_THEME) })?;
+        let highlighting = css_for_theme_with_class_style(theme, CLASS_STYLE)
+            .map_err(|e| ExemplifyError::Highlighting { message: e.to_string() })?;
+
+        Ok(HtmlStylesheet {
+            content: format!("{}\n{}", highlighting, CALLOUT_CSS)
+        })
+    }
+}
+
+impl Printable for HtmlStylesheet {
+    fn print(&self) -> String {
+        self.content.clone()
+    }
+
+    fn file_name(&self) -> String {
+        HTML_STYLESHEET_NAME.into()
+    }
+}
+
+pub fn map_to_html(input: Pin<Box<dyn Stream<Item=Example>>>, settings: HtmlSettings) -> Pin<Box<dyn Stream<Item=Result<HtmlExample, ExemplifyError>>>> {
+    Box::pin(input.map(move |example| {
+        // Escaped once, as it is used in several attributes
+        let anchor = escape_html(&match &example.id {
+            Some(id) => id.clone(),
+            _ => slugify(&example.name)
+        });
+
+        let content = transform_callouts(&example, &settings.callout_token, |number, comment| {
+            EnclosingComment::wrap(comment, format!("{}{}{}", CALLOUT_PLACEHOLDER_START, number, CALLOUT_PLACEHOLDER_END))
+        })?;
+
+        let code = highlight(&content.0, &example.language)?;
+        let code = insert_callout_badges(code, &anchor);
+
+        let title = match &example.title {
+            Some(title) => vec![format!("<div class=\"exemplify-title\">{}</div>", escape_html(title))],
+            _ => vec![]
+        };
+
+        let callouts = match content.1.len() {
+            0 => vec![],
+            _ => vec![
+                vec!["<ol class=\"exemplify-callouts\">".to_string()],
+                content.1.iter()
+                    .map(|callout| format!("<li id=\"{}-callout-{}\">{}</li>", anchor, callout.number, escape_html(&callout.text)))
+                    .collect(),
+                vec!["</ol>".to_string()]
+            ].into_iter().flatten().collect()
+        };
+
+        let language = match &example.language {
+            Some(language) => format!(" data-language=\"{}\"", escape_html(language)),
+            _ => "".into()
+        };
+
+        Ok(HtmlExample {
+            inner: Example {
+                content: vec![
+                    vec![format!("<div class=\"exemplify-example\" id=\"{}\">", anchor)],
+                    title,
+                    vec![format!("<pre class=\"exemplify-code\"{}><code>{}</code></pre>", language, code)],
+                    callouts,
+                    vec!["</div>".to_string()]
+                ].into_iter().flatten().collect(),
+                name: example.name,
+                title: example.title,
+                language: example.language,
+                id: example.id,
+                sources: example.sources,
+                line_locations: Vec::new()
+            }
+        })
+    }))
+}
+
+fn highlight(lines: &[String], language: &Option<String>) -> Result<String, ExemplifyError> {
+    let syntax = language.as_ref()
+        .and_then(|language| SYNTAX_SET.find_syntax_by_token(language))
+        .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text());
+
+    let code = lines.join("\n");
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAX_SET, CLASS_STYLE);
+
+    for line in LinesWithEndings::from(&code) {
+        generator.parse_html_for_line_which_includes_newline(line)
+            .map_err(|e| ExemplifyError::Highlighting { message: e.to_string() })?;
+    }
+
+    Ok(generator.finalize())
+}
+
+fn insert_callout_badges(mut code: String, anchor: &str) -> String {
+    while let Some(start) = code.find(CALLOUT_PLACEHOLDER_START) {
+        let end = match code[start..].find(CALLOUT_PLACEHOLDER_END) {
+            Some(end) => start + end,
+            _ => break
+        };
+
+        let number = code[start + CALLOUT_PLACEHOLDER_START.len_utf8()..end].to_string();
+        let badge = format!("<a class=\"callout\" href=\"#{}-callout-{}\"><span class=\"callout\">{}</span></a>", anchor, number, number);
+
+        code.replace_range(start..end + CALLOUT_PLACEHOLDER_END.len_utf8(), badge.as_str());
+    }
+
+    code
+}
+
+fn slugify(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect()
+}
+
+/// Escape text for use in html content and attribute values
+pub fn escape_html(input: &str) -> String {
+    input.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+const CALLOUT_CSS: &str = "\
+.exemplify-title {
+    font-weight: bold;
+}
+
+a.callout {
+    text-decoration: none;
+}
+
+span.callout {
+    display: inline-block;
+    min-width: 1.2em;
+    padding: 0 0.2em;
+    border-radius: 0.6em;
+    background-color: #444;
+    color: #fff;
+    font-size: 0.8em;
+    text-align: center;
+}
+";
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_html_transform() {
+        let example = Example::new(
+            "some/example".into(),
+            vec![
+                "let a = \"<b>\"; // ##exemplify-callout##{value=\"A <tag>\"}".into()
+            ],
+            Some("Title & more".into()),
+            Some("rust".into()),
+            None,
+            vec![]);
+
+        let mut output = map_to_html(Box::pin(futures::stream::iter(vec![example])), HtmlSettings {
+            callout_token: "##exemplify-callout##".into()
+        });
+
+        let result = output.next().await.unwrap().unwrap();
+        let html = result.print();
+
+        assert_eq!(result.file_name(), "some/example.html");
+        assert!(html.starts_with("<div class=\"exemplify-example\" id=\"some-example\">"));
+        assert!(html.contains("<div class=\"exemplify-title\">Title &amp; more</div>"));
+        assert!(html.contains("<span class=\"exemplify-source exemplify-rust\">"));
+        assert!(html.contains("&lt;b&gt;"));
+        assert!(html.contains("<a class=\"callout\" href=\"#some-example-callout-1\"><span class=\"callout\">1</span></a>"));
+        assert!(html.contains("<li id=\"some-example-callout-1\">A &lt;tag&gt;</li>"));
+        assert!(!html.contains(CALLOUT_PLACEHOLDER_START));
+    }
+
+    #[tokio::test]
+    async fn test_html_anchor_escaping() {
+        let example = Example::new(
+            "example".into(),
+            vec!["let a = 1; // ##exemplify-callout##{value=\"First\"}".into()],
+            None,
+            None,
+            Some("a\"<b".into()),
+            vec![]);
+
+        let mut output = map_to_html(Box::pin(futures::stream::iter(vec![example])), HtmlSettings {
+            callout_token: "##exemplify-callout##".into()
+        });
+
+        let html = output.next().await.unwrap().unwrap().print();
+
+        assert!(html.starts_with("<div class=\"exemplify-example\" id=\"a&quot;&lt;b\">"));
+        assert!(html.contains("href=\"#a&quot;&lt;b-callout-1\""));
+        assert!(html.contains("<li id=\"a&quot;&lt;b-callout-1\">"));
+        assert!(!html.contains("a\"<b"));
+    }
+}
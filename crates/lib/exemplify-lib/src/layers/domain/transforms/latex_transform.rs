@@ -0,0 +1,283 @@
+use std::pin::Pin;
+use std::str::FromStr;
+
+use futures::{Stream, StreamExt};
+use crate::layers::domain::entities::{Example, Printable};
+use crate::layers::domain::error::ExemplifyError;
+use crate::layers::domain::transforms::callouts::transform_callouts;
+use crate::layers::domain::transforms::markers::EnclosingComment;
+
+/// The LaTeX package used to typeset the source listing
+#[derive(Clone, Copy)]
+pub enum LatexEnvironment {
+    Listings,
+    Minted
+}
+
+impl FromStr for LatexEnvironment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "listings" => Ok(LatexEnvironment::Listings),
+            "minted" => Ok(LatexEnvironment::Minted),
+            _ => Err("invalid latex environment".into())
+        }
+    }
+}
+
+impl LatexEnvironment {
+    /// Delimiters used to escape from the listing back to LaTeX
+    fn escape_delimiters(&self) -> (&'static str, &'static str) {
+        match self {
+            LatexEnvironment::Listings => ("(*@", "@*)"),
+            LatexEnvironment::Minted => ("§", "§")
+        }
+    }
+}
+
+pub struct LatexSettings {
+    pub callout_token: String,
+    pub environment: LatexEnvironment
+}
+
+pub struct LatexExample {
+    inner: Example
+}
+
+impl Printable for LatexExample {
+    fn print(&self) -> String {
+        self.inner.print()
+    }
+
+    fn file_name(&self) -> String {
+        format!("{}.tex", self.inner.name)
+    }
+
+    fn example_name(&self) -> Option<String> {
+        self.inner.example_name()
+    }
+}
+
+pub fn map_to_latex(input: Pin<Box<dyn Stream<Item=Example>>>, settings: LatexSettings) -> Pin<Box<dyn Stream<Item=Result<LatexExample, ExemplifyError>>>> {
+    Box::pin(input.map(move |example| {
+        let header = create_latex_source_header(&settings, &example);
+        let footer = create_latex_source_footer(&settings, &example);
+
+        let (escape_start, escape_end) = settings.environment.escape_delimiters();
+
+        let content = transform_callouts(&example, &settings.callout_token, |number, comment| {
+            EnclosingComment::wrap(comment, format!("{}\\circled{{{}}}{}", escape_start, number, escape_end))
+        })?;
+
+        let callouts = match content.1.len() {
+            0 => vec![],
+            _ => vec![
+                vec!["\\begin{description}".to_string()],
+                content.1.iter()
+                    .map(|callout| format!("\\item[\\circled{{{}}}] {}", callout.number, escape_latex(&callout.text)))
+                    .collect(),
+                vec!["\\end{description}".to_string()]
+            ].into_iter().flatten().collect()
+        };
+
+        Ok(LatexExample {
+            inner: Example {
+                name: example.name,
+                content: vec![
+                    header,
+                    content.0,
+                    footer,
+                    callouts
+                ].into_iter().flatten().collect(),
+                title: example.title,
+                language: example.language,
+                id: example.id,
+                sources: example.sources,
+                line_locations: Vec::new()
+            }
+        })
+    }))
+}
+
+fn create_latex_source_header(settings: &LatexSettings, example: &Example) -> Vec<String> {
+    let (escape_start, escape_end) = settings.environment.escape_delimiters();
+
+    match settings.environment {
+        LatexEnvironment::Listings => {
+            let options: Vec<String> = vec![
+                example.language.as_deref().and_then(listings_language).map(|language| format!("language={}", language)),
+                example.title.as_ref().map(|title| format!("caption={{{}}}", escape_latex(title))),
+                example.id.as_ref().map(|id| format!("label={{{}}}", id)),
+                Some(format!("escapeinside={{{}}}{{{}}}", escape_start, escape_end))
+            ].into_iter().flatten().collect();
+
+            vec![format!("\\begin{{lstlisting}}[{}]", options.join(", "))]
+        }
+        LatexEnvironment::Minted => {
+            let language = match &example.language {
+                Some(language) => language.clone(),
+                _ => "text".into()
+            };
+
+            vec![
+                "\\begin{listing}[H]".into(),
+                format!("\\begin{{minted}}[escapeinside={}{}]{{{}}}", escape_start, escape_end, language)
+            ]
+        }
+    }
+}
+
+fn create_latex_source_footer(settings: &LatexSettings, example: &Example) -> Vec<String> {
+    match settings.environment {
+        LatexEnvironment::Listings => vec!["\\end{lstlisting}".into()],
+        LatexEnvironment::Minted => {
+            let title = match &example.title {
+                Some(title) => vec![format!("\\caption{{{}}}", escape_latex(title))],
+                _ => vec![]
+            };
+
+            let id = match &example.id {
+                Some(id) => vec![format!("\\label{{{}}}", id)],
+                _ => vec![]
+            };
+
+            vec![
+                vec!["\\end{minted}".into()],
+                title,
+                id,
+                vec!["\\end{listing}".into()]
+            ].into_iter().flatten().collect()
+        }
+    }
+}
+
+/// The listings dialect of a language. Languages listings does not know are left out, as naming them stops the LaTeX run
+fn listings_language(language: &str) -> Option<&'static str> {
+    let dialect = match language.to_lowercase().as_str() {
+        "c" => "C",
+        "cpp" | "c++" => "C++",
+        "csharp" | "c#" => "[Sharp]C",
+        "objectivec" | "objective-c" => "[Objective]C",
+        "java" => "Java",
+        "python" => "Python",
+        "ruby" => "Ruby",
+        "perl" => "Perl",
+        "php" => "PHP",
+        "bash" => "bash",
+        "sh" | "shell" => "sh",
+        "sql" => "SQL",
+        "haskell" => "Haskell",
+        "ocaml" => "Caml",
+        "erlang" => "erlang",
+        "lisp" => "Lisp",
+        "ada" => "Ada",
+        "fortran" => "Fortran",
+        "pascal" => "Pascal",
+        "delphi" => "Delphi",
+        "matlab" => "Matlab",
+        "octave" => "Octave",
+        "r" => "R",
+        "tcl" => "tcl",
+        "awk" => "Awk",
+        "make" | "makefile" => "make",
+        "html" => "HTML",
+        "xml" => "XML",
+        "xslt" => "XSLT",
+        "tex" | "latex" => "TeX",
+        "vhdl" => "VHDL",
+        "verilog" => "Verilog",
+        "prolog" => "Prolog",
+        _ => return None
+    };
+
+    Some(dialect)
+}
+
+/// Escape characters with special meaning in LaTeX text mode
+pub fn escape_latex(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+
+    for c in input.chars() {
+        match c {
+            '\\' => output.push_str("\\textbackslash{}"),
+            '~' => output.push_str("\\textasciitilde{}"),
+            '^' => output.push_str("\\textasciicircum{}"),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                output.push('\\');
+                output.push(c);
+            }
+            _ => output.push(c)
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn example() -> Example {
+        Example::new(
+            "example".into(),
+            vec![
+                "let a = 1; // ##exemplify-callout##{value=\"Costs 5$ & more\"}".into()
+            ],
+            Some("Step_1: {setup} 100%".into()),
+            Some("rust".into()),
+            Some("lst:example".into()),
+            vec![])
+    }
+
+    #[tokio::test]
+    async fn test_listings_transform() {
+        let mut output = map_to_latex(Box::pin(futures::stream::iter(vec![example()])), LatexSettings {
+            callout_token: "##exemplify-callout##".into(),
+            environment: LatexEnvironment::Listings
+        });
+
+        let result = output.next().await.unwrap().unwrap();
+
+        assert_eq!(result.file_name(), "example.tex");
+        assert_eq!(result.print(), "\
+\\begin{lstlisting}[caption={Step\\_1: \\{setup\\} 100\\%}, label={lst:example}, escapeinside={(*@}{@*)}]
+let a = 1; // (*@\\circled{1}@*)
+\\end{lstlisting}
+\\begin{description}
+\\item[\\circled{1}] Costs 5\\$ \\& more
+\\end{description}");
+    }
+
+    #[test]
+    fn test_listings_language() {
+        let header = |language: &str| create_latex_source_header(
+            &LatexSettings { callout_token: "##exemplify-callout##".into(), environment: LatexEnvironment::Listings },
+            &Example::new("example".into(), vec![], None, Some(language.into()), None, vec![]))[0].clone();
+
+        assert_eq!(header("typescript"), "\\begin{lstlisting}[escapeinside={(*@}{@*)}]");
+        assert_eq!(header("cpp"), "\\begin{lstlisting}[language=C++, escapeinside={(*@}{@*)}]");
+    }
+
+    #[tokio::test]
+    async fn test_minted_transform() {
+        let mut output = map_to_latex(Box::pin(futures::stream::iter(vec![example()])), LatexSettings {
+            callout_token: "##exemplify-callout##".into(),
+            environment: LatexEnvironment::Minted
+        });
+
+        let result = output.next().await.unwrap().unwrap();
+
+        assert_eq!(result.print(), "\
+\\begin{listing}[H]
+\\begin{minted}[escapeinside=§§]{rust}
+let a = 1; // §\\circled{1}§
+\\end{minted}
+\\caption{Step\\_1: \\{setup\\} 100\\%}
+\\label{lst:example}
+\\end{listing}
+\\begin{description}
+\\item[\\circled{1}] Costs 5\\$ \\& more
+\\end{description}");
+    }
+}
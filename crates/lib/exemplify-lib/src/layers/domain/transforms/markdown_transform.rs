@@ -0,0 +1,160 @@
+use std::pin::Pin;
+
+use futures::{Stream, StreamExt};
+use crate::layers::domain::entities::{Example, Printable};
+use crate::layers::domain::error::ExemplifyError;
+use crate::layers::domain::transforms::callouts::transform_callouts;
+use crate::layers::domain::transforms::html_transform::escape_html;
+use crate::layers::domain::transforms::markers::EnclosingComment;
+
+pub struct MarkdownSettings {
+    pub callout_token: String
+}
+
+pub struct MarkdownExample {
+    inner: Example
+}
+
+impl Printable for MarkdownExample {
+    fn print(&self) -> String {
+        self.inner.print()
+    }
+
+    fn file_name(&self) -> String {
+        format!("{}.md", self.inner.name)
+    }
+
+    fn example_name(&self) -> Option<String> {
+        self.inner.example_name()
+    }
+}
+
+pub fn map_to_markdown(input: Pin<Box<dyn Stream<Item=Example>>>, settings: MarkdownSettings) -> Pin<Box<dyn Stream<Item=Result<MarkdownExample, ExemplifyError>>>> {
+    Box::pin(input.map(move |example| {
+        let content = transform_callouts(&example, &settings.callout_token, |number, comment| EnclosingComment::wrap(comment, format!("({})", number)))?;
+
+        let fence = code_fence(&content.0);
+        let header = create_markdown_source_header(&settings, &example, &fence);
+        let footer = create_markdown_source_footer(&settings, &fence);
+        let mut callouts: Vec<String> = content.1
+            .into_iter()
+            .map(|callout| format!("{}. {}", callout.number, callout.text))
+            .collect();
+
+        // Markdown needs a blank line between the fence and the footnote list
+        if !callouts.is_empty() {
+            callouts.insert(0, "".into());
+        }
+
+        Ok(MarkdownExample {
+            inner: Example {
+                name: example.name,
+                content: vec![
+                    header,
+                    content.0,
+                    footer,
+                    callouts
+                ].into_iter().flatten().collect(),
+                title: example.title,
+                language: example.language,
+                id: example.id,
+                sources: example.sources,
+                line_locations: Vec::new()
+            }
+        })
+    }))
+}
+
+/// A backtick fence longer than any run of backticks in the content, so the content cannot close the code block
+fn code_fence(content: &[String]) -> String {
+    let longest_run = content.iter()
+        .flat_map(|line| line.split(|character| character != '`'))
+        .map(|run| run.len())
+        .max()
+        .unwrap_or(0);
+
+    "`".repeat(std::cmp::max(3, longest_run + 1))
+}
+
+fn create_markdown_source_header(_settings: &MarkdownSettings, example: &Example, fence: &str) -> Vec<String> {
+    let id = match &example.id {
+        Some(id) => vec![format!("<a id=\"{}\"></a>", escape_html(id))],
+        _ => vec![]
+    };
+
+    let title = match &example.title {
+        Some(title) => vec![format!("**{}**", title), "".into()],
+        _ => vec![]
+    };
+
+    vec![
+        id,
+        title,
+        vec![format!("{}{}", fence, match &example.language {
+            Some(language) => language.clone(),
+            _ => "".into()
+        })]
+    ].into_iter().flatten().collect()
+}
+
+fn create_markdown_source_footer(_settings: &MarkdownSettings, fence: &str) -> Vec<String> {
+    vec![
+        fence.into()
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_markdown_transform() {
+        let example = Example::new(
+            "example".into(),
+            vec![
+                "let a = 1; // ##exemplify-callout##{value=\"First\"}".into(),
+                "let b = 2;".into()
+            ],
+            Some("A title".into()),
+            Some("rust".into()),
+            Some("an-id".into()),
+            vec![]);
+
+        let mut output = map_to_markdown(Box::pin(futures::stream::iter(vec![example])), MarkdownSettings {
+            callout_token: "##exemplify-callout##".into()
+        });
+
+        let result = output.next().await.unwrap().unwrap();
+
+        assert_eq!(result.file_name(), "example.md");
+        assert_eq!(result.print(), "\
+<a id=\"an-id\"></a>
+**A title**
+
+```rust
+let a = 1; // (1)
+let b = 2;
+```
+
+1. First");
+    }
+
+    #[tokio::test]
+    async fn test_markdown_anchor_escaping() {
+        let example = Example::new("example".into(), vec!["let a = 1;".into()], None, None, Some("say \"hi\" <b>".into()), vec![]);
+
+        let mut output = map_to_markdown(Box::pin(futures::stream::iter(vec![example])), MarkdownSettings {
+            callout_token: "##exemplify-callout##".into()
+        });
+
+        let result = output.next().await.unwrap().unwrap();
+
+        assert!(result.print().starts_with("<a id=\"say &quot;hi&quot; &lt;b&gt;\"></a>\n"));
+    }
+
+    #[test]
+    fn test_code_fence() {
+        assert_eq!(code_fence(&["let a = 1;".into()]), "```");
+        assert_eq!(code_fence(&["Use `code` or".into(), "````".into(), "```rust".into()]), "`````");
+    }
+}
@@ -0,0 +1,133 @@
+use std::ops::Range;
+
+/// Line comment delimiters, longest first so doc comments lose their whole delimiter
+const LINE_COMMENTS: &[&str] = &["//!", "///", "//", "#", "--"];
+const BLOCK_COMMENTS: &[(&str, &str)] = &[("<!--", "-->"), ("/*", "*/")];
+
+/// A block comment holding nothing but a marker
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EnclosingComment {
+    pub start: &'static str,
+    pub end: &'static str
+}
+
+impl EnclosingComment {
+    /// Put text in place of the marker, keeping the comment around it
+    pub fn wrap(comment: Option<EnclosingComment>, text: String) -> String {
+        match comment {
+            Some(comment) => format!("{} {} {}", comment.start, text, comment.end),
+            _ => text
+        }
+    }
+}
+
+/// The block comment around the marker at `start..end` of the line, with its byte range,
+/// if the comment holds nothing else
+pub fn enclosing_comment(line: &str, start: usize, end: usize) -> Option<(EnclosingComment, Range<usize>)> {
+    let before = line[..start].trim_end();
+    let after = line[end..].trim_start();
+
+    BLOCK_COMMENTS.iter()
+        .find(|(comment_start, comment_end)| before.ends_with(comment_start) && after.starts_with(comment_end))
+        .map(|(comment_start, comment_end)| {
+            let comment_end_position = line.len() - after.len() + comment_end.len();
+
+            (EnclosingComment { start: comment_start, end: comment_end }, before.len() - comment_start.len()..comment_end_position)
+        })
+}
+
+/// Remove the marker found at `start..end` of the line.
+/// A comment left empty by the removal is removed as well, so
+/// `let a = 1; // ##exemplify-callout##{value="a"}` becomes `let a = 1;`
+pub fn strip_marker(line: &str, start: usize, end: usize) -> String {
+    let before = line[..start].trim_end();
+    let after = line[end..].trim_start();
+
+    if after.is_empty() {
+        if let Some(delimiter) = LINE_COMMENTS.iter().find(|delimiter| before.ends_with(*delimiter)) {
+            return before[..before.len() - delimiter.len()].trim_end().to_string();
+        }
+    }
+
+    if let Some((_, comment)) = enclosing_comment(line, start, end) {
+        return join_remains(&line[..comment.start], &line[comment.end..]);
+    }
+
+    join_remains(&line[..start], &line[end..])
+}
+
+/// Remove every occurrence of the given marker tokens from the content, along with the attribute block following them
+pub fn strip_markers(input: Vec<String>, tokens: &[&str]) -> Vec<String> {
+    input.into_iter()
+        .map(|mut line| {
+            for token in tokens {
+                while let Some(start) = line.find(token) {
+                    let end = start + token.len() + attribute_block_length(&line[start + token.len()..]);
+
+                    line = strip_marker(&line, start, end);
+                }
+            }
+
+            line
+        })
+        .collect()
+}
+
+/// Length of the `{...}` block at the start of the input, if there is one
+pub fn attribute_block_length(input: &str) -> usize {
+    if !input.starts_with('{') {
+        return 0;
+    }
+
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (index, character) in input.char_indices() {
+        match character {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '}' if !in_string => return index + 1,
+            _ => {}
+        }
+    }
+
+    0
+}
+
+/// Join the text around a removed marker, keeping the whitespace in front of it
+fn join_remains(before: &str, after: &str) -> String {
+    match after.trim().is_empty() {
+        true => before.trim_end().to_string(),
+        false => format!("{}{}", before, after.trim_start())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_strip_markers() {
+        let content = vec![
+            "let a = 1; // ##exemplify-callout##{value=\"a}\"}",
+            "    a = 1 # ##exemplify-callout##",
+            "<p/> <!-- ##exemplify-callout##{value=\"p\"} -->",
+            "call(/* ##exemplify-callout## */ a);",
+            "let b = 2; // keep ##exemplify-callout##",
+            "let c = 3; //"
+        ].into_iter().map(String::from).collect();
+
+        assert_eq!(enclosing_comment("a <!-- m --> b", 7, 8), Some((EnclosingComment { start: "<!--", end: "-->" }, 2..12)));
+        assert_eq!(enclosing_comment("a /* m x */", 5, 6), None);
+
+        assert_eq!(strip_markers(content, &["##exemplify-callout##"]), vec![
+            "let a = 1;",
+            "    a = 1",
+            "<p/>",
+            "call(a);",
+            "let b = 2; // keep",
+            "let c = 3; //"
+        ]);
+    }
+}
@@ -1 +1,8 @@
 pub mod asciidoctor_transform;
+pub mod callouts;
+pub mod html_transform;
+pub mod latex_transform;
+pub mod markdown_transform;
+pub mod markers;
+pub mod plain_transform;
+pub mod rst_transform;
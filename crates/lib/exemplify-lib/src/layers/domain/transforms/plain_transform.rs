@@ -0,0 +1,41 @@
+use std::pin::Pin;
+
+use futures::{Stream, StreamExt};
+use crate::layers::domain::entities::Example;
+use crate::layers::domain::transforms::markers::strip_markers;
+
+pub struct PlainSettings {
+    pub callout_token: String
+}
+
+/// Plain output has nowhere to show callouts, so their markers are removed from the code
+pub fn map_to_plain(input: Pin<Box<dyn Stream<Item=Example>>>, settings: PlainSettings) -> Pin<Box<dyn Stream<Item=Example>>> {
+    Box::pin(input.map(move |example| {
+        Example {
+            content: strip_markers(example.content, &[&settings.callout_token]),
+            ..example
+        }
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_plain_transform() {
+        let example = Example::new(
+            "example".into(),
+            vec![
+                "let a = 1; // ##exemplify-callout##".into(),
+                "let b = 2; // ##exemplify-callout##{value=\"Second\"}".into()
+            ],
+            None, None, None, vec![]);
+
+        let mut output = map_to_plain(Box::pin(futures::stream::iter(vec![example])), PlainSettings {
+            callout_token: "##exemplify-callout##".into()
+        });
+
+        assert_eq!(output.next().await.unwrap().content, vec!["let a = 1;", "let b = 2;"]);
+    }
+}
@@ -0,0 +1,135 @@
+use std::pin::Pin;
+
+use futures::{Stream, StreamExt};
+use crate::layers::domain::entities::{Example, Printable};
+use crate::layers::domain::error::ExemplifyError;
+use crate::layers::domain::transforms::callouts::transform_callouts;
+use crate::layers::domain::transforms::markers::EnclosingComment;
+
+/// Indentation used for directive options and content
+const RST_INDENT: &str = "   ";
+
+pub struct RstSettings {
+    pub callout_token: String
+}
+
+pub struct RstExample {
+    inner: Example
+}
+
+impl Printable for RstExample {
+    fn print(&self) -> String {
+        self.inner.print()
+    }
+
+    fn file_name(&self) -> String {
+        format!("{}.rst", self.inner.name)
+    }
+
+    fn example_name(&self) -> Option<String> {
+        self.inner.example_name()
+    }
+}
+
+pub fn map_to_rst(input: Pin<Box<dyn Stream<Item=Example>>>, settings: RstSettings) -> Pin<Box<dyn Stream<Item=Result<RstExample, ExemplifyError>>>> {
+    Box::pin(input.map(move |example| {
+        let header = create_rst_source_header(&settings, &example);
+
+        let content = transform_callouts(&example, &settings.callout_token, |number, comment| EnclosingComment::wrap(comment, format!("({})", number)))?;
+        let body = content.0
+            .into_iter()
+            .map(|line| match line.len() {
+                0 => line,
+                _ => format!("{}{}", RST_INDENT, line)
+            })
+            .collect();
+
+        let mut callouts: Vec<String> = content.1
+            .into_iter()
+            .map(|callout| format!("{}. {}", callout.number, callout.text))
+            .collect();
+
+        // The list must be separated from the directive body by a blank line
+        if !callouts.is_empty() {
+            callouts.insert(0, "".into());
+        }
+
+        Ok(RstExample {
+            inner: Example {
+                name: example.name,
+                content: vec![
+                    header,
+                    body,
+                    callouts
+                ].into_iter().flatten().collect(),
+                title: example.title,
+                language: example.language,
+                id: example.id,
+                sources: example.sources,
+                line_locations: Vec::new()
+            }
+        })
+    }))
+}
+
+fn create_rst_source_header(_settings: &RstSettings, example: &Example) -> Vec<String> {
+    let directive = match &example.language {
+        Some(language) => format!(".. code-block:: {}", language),
+        _ => ".. code-block::".into()
+    };
+
+    let title = match &example.title {
+        Some(title) => vec![format!("{}:caption: {}", RST_INDENT, title)],
+        _ => vec![]
+    };
+
+    let id = match &example.id {
+        Some(id) => vec![format!("{}:name: {}", RST_INDENT, id)],
+        _ => vec![]
+    };
+
+    vec![
+        vec![directive],
+        title,
+        id,
+        vec!["".into()]
+    ].into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rst_transform() {
+        let example = Example::new(
+            "example".into(),
+            vec![
+                "def foo():".into(),
+                "".into(),
+                "    return 1 # ##exemplify-callout##{value=\"Returns one\"}".into()
+            ],
+            Some("A title".into()),
+            Some("python".into()),
+            Some("an-id".into()),
+            vec![]);
+
+        let mut output = map_to_rst(Box::pin(futures::stream::iter(vec![example])), RstSettings {
+            callout_token: "##exemplify-callout##".into()
+        });
+
+        let result = output.next().await.unwrap().unwrap();
+
+        assert_eq!(result.file_name(), "example.rst");
+        assert_eq!(result.print(), "\
+.. code-block:: python
+   :caption: A title
+   :name: an-id
+
+   def foo():
+
+       return 1 # (1)
+
+1. Returns one");
+    }
+}
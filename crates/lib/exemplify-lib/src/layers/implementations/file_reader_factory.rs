@@ -1,27 +1,111 @@
+use std::collections::HashMap;
 use std::fs::File;
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 
 
 
 
 
+use crate::layers::domain::error::ExemplifyError;
 use crate::layers::domain::reader_factory::{ReaderFactory, ReaderContext};
 
-pub struct FileReaderFactory {}
+/// A folder containing source files.
+/// Files below it are named by the label of the root followed by their path relative to the root
+#[derive(Clone)]
+pub struct SourceRoot {
+    pub label: String,
+    pub path: PathBuf
+}
+
+/// Create the roots for the given source folders, labeled by their folder name.
+/// Roots sharing a folder name are labeled by the path they were given as instead
+pub fn source_roots(paths: &[String]) -> Vec<SourceRoot> {
+    let roots: Vec<(String, PathBuf)> = paths.iter()
+        .map(|path| (path.clone(), Path::new(path).canonicalize().unwrap_or_else(|_| PathBuf::from(path))))
+        .collect();
+
+    let folder_name = |path: &Path| path.file_name().map(|name| name.to_string_lossy().to_string());
+
+    let mut label_count: HashMap<Option<String>, usize> = HashMap::new();
+
+    for (_, path) in &roots {
+        *label_count.entry(folder_name(path)).or_default() += 1;
+    }
+
+    roots.into_iter()
+        .map(|(given_path, path)| {
+            let label = match folder_name(&path) {
+                Some(name) if label_count[&Some(name.clone())] == 1 => name,
+                _ => given_path.trim_end_matches('/').to_string()
+            };
+
+            SourceRoot { label, path }
+        })
+        .collect()
+}
+
+/// Find the file a source name given by `FileReaderFactory` refers to
+pub fn resolve_source_name(roots: &[SourceRoot], source_name: &str) -> PathBuf {
+    for root in roots {
+        if let Ok(relative_path) = Path::new(source_name).strip_prefix(&root.label) {
+            return root.path.join(relative_path);
+        }
+    }
+
+    PathBuf::from(source_name)
+}
+
+pub struct FileReaderFactory {
+    roots: Vec<SourceRoot>
+}
+
+impl FileReaderFactory {
+    pub fn new(roots: Vec<SourceRoot>) -> FileReaderFactory {
+        FileReaderFactory { roots }
+    }
+
+    fn source_name(&self, file_path: &Path) -> String {
+        for root in &self.roots {
+            if let Ok(relative_path) = file_path.strip_prefix(&root.path) {
+                return Path::new(&root.label).join(relative_path).to_string_lossy().to_string();
+            }
+        }
+
+        file_path.to_string_lossy().to_string()
+    }
+}
 
 impl ReaderFactory<File> for FileReaderFactory {
-    fn make_reader(&self, name: String) -> Result<ReaderContext<File>, String> {
+    fn make_reader(&self, name: String) -> Result<ReaderContext<File>, ExemplifyError> {
         let file_path = Path::new(&name);
 
         if !file_path.is_file() {
-            return Err(format!("{} is not a file", name).into());
+            return Err(ExemplifyError::NotAFile { path: name });
         }
 
         Ok(ReaderContext {
-            source_name: name.clone(),
-            reader: std::fs::File::open(file_path).map_err(|e| e.to_string())?
+            source_name: self.source_name(file_path),
+            reader: std::fs::File::open(file_path).map_err(|e| ExemplifyError::io(&name, e))?
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_root_relative_source_names() {
+        let roots = source_roots(&["/repos/sdk".into(), "/repos/a/src".into(), "/repos/b/src/".into()]);
+        let labels: Vec<&str> = roots.iter().map(|root| root.label.as_str()).collect();
+
+        assert_eq!(labels, vec!["sdk", "/repos/a/src", "/repos/b/src"]);
+
+        let factory = FileReaderFactory::new(roots.clone());
+
+        assert_eq!(factory.source_name(Path::new("/repos/sdk/lib/main.rs")), "sdk/lib/main.rs");
+        assert_eq!(resolve_source_name(&roots, "sdk/lib/main.rs"), PathBuf::from("/repos/sdk/lib/main.rs"));
+    }
+}
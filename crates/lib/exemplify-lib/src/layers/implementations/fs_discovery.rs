@@ -1,43 +1,256 @@
 use std::path::Path;
-use std::pin::Pin;
 
-use futures::{Stream};
+use ignore::WalkBuilder;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::overrides::{Override, OverrideBuilder};
 
-pub fn discover_fs_files(root_folder: String, file_patterns: &Vec<String>) -> Result<Pin<Box<dyn Stream<Item=Result<String, String>>>>, String> {
-    let path = Path::new(root_folder.as_str());
-    let files = recursively_find_files(path, file_patterns)?;
+use crate::layers::domain::error::ExemplifyError;
+use crate::layers::domain::reader_stream::SourceNameStream;
+use crate::layers::implementations::file_reader_factory::SourceRoot;
 
-    Ok(Box::pin(futures::stream::iter(files.into_iter().map(|f| Ok(f)))))
+/// Decides which files below a root folder are scanned for examples
+#[derive(Clone, Default)]
+pub struct DiscoverySettings {
+    /// Extensions of the files to scan. If empty, the extension is not checked
+    pub extensions: Vec<String>,
+    /// Globs relative to the root folder; if any are given, only matching files are scanned.
+    /// A glob starting with `!` excludes the matching files instead
+    pub include: Vec<String>,
+    /// Globs relative to the root folder of files to skip
+    pub exclude: Vec<String>,
+    /// Skip the files ignored by `.gitignore` and `.ignore` files, `.git/info/exclude` and the global git excludes file
+    pub use_ignore_files: bool
 }
 
-fn recursively_find_files(root_folder: &Path, file_patterns: &Vec<String>) -> Result<Vec<String>, String> {
+impl DiscoverySettings {
+    /// Whether discovery below `root_folder` would select the file at `path`, which may no longer exist.
+    /// Both paths must be absolute, or both relative to the same folder
+    pub fn matches(&self, root_folder: &Path, path: &Path) -> Result<bool, ExemplifyError> {
+        if self.extensions.is_empty() && self.include.is_empty() {
+            return Ok(false);
+        }
+
+        let relative_path = match path.strip_prefix(root_folder) {
+            Ok(relative_path) => relative_path,
+            _ => return Ok(false)
+        };
+
+        if relative_path.components().any(|component| component.as_os_str() == ".git") || !self.matches_extension(path) {
+            return Ok(false);
+        }
+
+        let overrides = self.overrides(root_folder)?;
+        let global = match self.use_ignore_files {
+            true => Gitignore::global().0,
+            false => Gitignore::empty()
+        };
+
+        // The walker skips excluded and ignored folders without entering them, so the folders of the file are checked as well.
+        // As with the walker, the include and exclude globs take precedence over the ignore files
+        let skipped = path.ancestors()
+            .take_while(|entry| entry.starts_with(root_folder) && *entry != root_folder)
+            .any(|entry| {
+                let is_dir = entry != path;
+
+                match overrides.matched(entry, is_dir) {
+                    matched if !matched.is_none() => matched.is_ignore(),
+                    _ => self.use_ignore_files && is_ignored(entry, is_dir, &global)
+                }
+            });
+
+        Ok(!skipped)
+    }
+
+    fn matches_extension(&self, path: &Path) -> bool {
+        if self.extensions.is_empty() {
+            return true;
+        }
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) => self.extensions.iter().any(|ext| ext == extension),
+            _ => false
+        }
+    }
+
+    fn overrides(&self, root_folder: &Path) -> Result<Override, ExemplifyError> {
+        let mut builder = OverrideBuilder::new(root_folder);
+
+        let excludes = self.exclude.iter()
+            .map(|glob| format!("!{}", glob.trim_start_matches('!')));
+
+        for glob in self.include.iter().cloned().chain(excludes) {
+            builder.add(&glob).map_err(|e| ExemplifyError::InvalidGlob { glob: glob.clone(), message: e.to_string() })?;
+        }
+
+        builder.build().map_err(|e| ExemplifyError::InvalidGlob { glob: self.include.join(" "), message: e.to_string() })
+    }
+}
+
+/// Find the files to scan below the root folder, sorted by path.
+/// With neither extensions nor include globs given, no files are selected
+pub fn discover_fs_files(root_folder: String, settings: &DiscoverySettings) -> Result<SourceNameStream, ExemplifyError> {
+    let files = find_sorted_files(Path::new(root_folder.as_str()), settings)?;
+
+    Ok(Box::pin(futures::stream::iter(files.into_iter().map(Ok))))
+}
+
+/// Find the files to scan below each of the roots, in the order the roots are given
+pub fn discover_fs_roots(roots: &[SourceRoot], settings: &DiscoverySettings) -> Result<SourceNameStream, ExemplifyError> {
+    let mut files = Vec::new();
+
+    for root in roots {
+        files.append(&mut find_sorted_files(&root.path, settings)?);
+    }
+
+    Ok(Box::pin(futures::stream::iter(files.into_iter().map(Ok))))
+}
+
+fn find_sorted_files(root_folder: &Path, settings: &DiscoverySettings) -> Result<Vec<String>, ExemplifyError> {
+    if settings.extensions.is_empty() && settings.include.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut files = find_files(root_folder, settings)?;
+
+    // The directory listing order depends on the file system, so sort for reproducible runs
+    files.sort();
+
+    Ok(files)
+}
+
+fn find_files(root_folder: &Path, settings: &DiscoverySettings) -> Result<Vec<String>, ExemplifyError> {
     let mut files: Vec<String> = Vec::new();
+    let folder_name = root_folder.to_string_lossy();
 
-    for entry in std::fs::read_dir(root_folder).map_err(|e| e.to_string())? {
-        let entry = entry.map_err(|e| e.to_string())?;
+    let walker = WalkBuilder::new(root_folder)
+        .standard_filters(settings.use_ignore_files)
+        // Hidden files were always scanned, so only the ignore files decide what to skip
+        .hidden(false)
+        .require_git(false)
+        .overrides(settings.overrides(root_folder)?)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build();
 
-        let path = entry.path();
+    for entry in walker {
+        let entry = entry.map_err(|e| ExemplifyError::io(&folder_name, std::io::Error::other(e)))?;
 
-        let metadata = std::fs::symlink_metadata(&path).map_err(|e| e.to_string())?;
+        // Symbolic links are not followed, and have a file type of their own
+        if !entry.file_type().map(|file_type| file_type.is_file()).unwrap_or(false) {
+            continue;
+        }
 
-        if metadata.file_type().is_symlink() {
+        let path = entry.path();
+
+        if !settings.matches_extension(path) {
             continue;
         }
 
-        if path.is_file() {
-            for ext in file_patterns {
-                let string_path = path.to_str().ok_or("".to_string())?.to_string();
+        let string_path = path.to_str().ok_or_else(|| ExemplifyError::InvalidFileName { path: path.to_string_lossy().to_string() })?;
 
-                if path.extension().ok_or("Invalid filename")?.to_str().ok_or("Invalid filename")? == ext {
-                    files.push(string_path)
-                }
-            }
-        } else if path.is_dir() {
-            let mut sub_folder_files = recursively_find_files(&entry.path(), file_patterns)?;
+        files.push(string_path.to_string());
+    }
+
+    Ok(files)
+}
 
-            files.append(&mut sub_folder_files);
+/// Match an entry against the ignore files of the folders holding it, with the precedence of the walker:
+/// `.ignore` files over `.gitignore` files over `.git/info/exclude` over the global git excludes file.
+/// For each kind the files of deeper folders take precedence, and git ignore files above the repository are left out
+fn is_ignored(path: &Path, is_dir: bool, global: &Gitignore) -> bool {
+    let folders: Vec<&Path> = path.ancestors().skip(1).collect();
+    let repository_end = folders.iter().position(|folder| folder.join(".git").exists()).map_or(folders.len(), |position| position + 1);
+    let repository_folders = &folders[..repository_end];
+
+    let matchers = folders.iter().map(|folder| Gitignore::new(folder.join(".ignore")).0)
+        .chain(repository_folders.iter().map(|folder| Gitignore::new(folder.join(".gitignore")).0))
+        .chain(repository_folders.iter().map(|folder| git_exclude(folder)));
+
+    for matcher in matchers {
+        let matched = matcher.matched(path, is_dir);
+
+        if !matched.is_none() {
+            return matched.is_ignore();
         }
     }
 
-    Ok(files)
+    global.matched(path, is_dir).is_ignore()
+}
+
+/// The `.git/info/exclude` file of a repository, whose patterns are relative to the repository folder
+fn git_exclude(folder: &Path) -> Gitignore {
+    let mut builder = GitignoreBuilder::new(folder);
+
+    builder.add(folder.join(".git/info/exclude"));
+
+    builder.build().unwrap_or_else(|_| Gitignore::empty())
+}
+
+#[cfg(test)]
+mod test {
+    use futures::StreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_discovery_filters() {
+        let root = std::env::temp_dir().join(format!("exemplify-discovery-{}", std::process::id()));
+
+        for folder in &["src/generated", "node_modules/lib"] {
+            std::fs::create_dir_all(root.join(folder)).unwrap();
+        }
+
+        for file in &["src/main.rs", "src/generated/api.rs", "node_modules/lib/index.rs", "Makefile", "notes.txt"] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+
+        std::fs::write(root.join(".gitignore"), "node_modules/\n").unwrap();
+        std::fs::create_dir_all(root.join(".git/info")).unwrap();
+        std::fs::write(root.join(".git/info/exclude"), "src/local.rs\n").unwrap();
+        std::fs::write(root.join("src/local.rs"), "").unwrap();
+
+        let find = |settings: DiscoverySettings| {
+            let root_name = root.to_string_lossy().to_string();
+
+            async move {
+                let files: Vec<String> = discover_fs_files(root_name.clone(), &settings).unwrap()
+                    .map(|file| file.unwrap()[root_name.len() + 1..].to_string())
+                    .collect().await;
+
+                files
+            }
+        };
+
+        assert_eq!(find(DiscoverySettings { extensions: vec!["rs".into()], use_ignore_files: true, ..Default::default() }).await,
+                   vec!["src/generated/api.rs", "src/main.rs"]);
+
+        assert_eq!(find(DiscoverySettings { extensions: vec!["rs".into()], ..Default::default() }).await,
+                   vec!["node_modules/lib/index.rs", "src/generated/api.rs", "src/local.rs", "src/main.rs"]);
+
+        // Include globs take precedence over the ignore files
+        assert_eq!(find(DiscoverySettings {
+            include: vec!["src/**/*.rs".into(), "Makefile".into()],
+            exclude: vec!["**/generated/**".into()],
+            use_ignore_files: true,
+            ..Default::default()
+        }).await, vec!["Makefile", "src/local.rs", "src/main.rs"]);
+
+        let matches = |settings: &DiscoverySettings, file: &str| settings.matches(&root, &root.join(file)).unwrap();
+        let settings = DiscoverySettings {
+            extensions: vec!["rs".into()],
+            exclude: vec!["**/generated/**".into()],
+            use_ignore_files: true,
+            ..Default::default()
+        };
+
+        assert!(matches(&settings, "src/main.rs"));
+        assert!(matches(&settings, "src/removed.rs"));
+        assert!(!matches(&settings, "src/generated/api.rs"));
+        assert!(!matches(&settings, "node_modules/lib/index.rs"));
+        assert!(!matches(&settings, "src/local.rs"));
+        assert!(matches(&DiscoverySettings { include: vec!["src/**/*.rs".into()], use_ignore_files: true, ..Default::default() }, "src/local.rs"));
+        assert!(!matches(&settings, "notes.txt"));
+        assert!(!matches(&DiscoverySettings::default(), "src/main.rs"));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
 }